@@ -1,26 +1,63 @@
-use std::{
-    fs::{self, File},
-    io::Write,
-};
+use std::io::{self, ErrorKind};
 
-use rand::{rngs::ThreadRng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use ratatui::text::Line;
+use tokio::sync::mpsc::UnboundedSender;
 
-use crate::misc::{get_chatgpt_words, get_default_sentences};
+use crate::{
+    cli::{Args, Mode},
+    config::{Config, KeyBindings},
+    misc::get_chatgpt_words,
+    storage::Storage,
+};
 
-use self::typing_screen::{JSONResults, TypingMode};
+use self::{
+    confusions::ConfusionsState,
+    ngrams::NgramsState,
+    profiles::ProfilesState,
+    progress::ProgressState,
+    replay::{Replay, ReplayList},
+    typing_screen::{Failure, Ghost, Guess, JSONResults, LiveStats, TypingMode},
+};
 
+pub mod confusions;
+pub mod history;
+pub mod ngrams;
+pub mod profiles;
+pub mod progress;
+pub mod replay;
 pub mod typing_screen;
 
+// how many letters of the timed modes are always ahead of the user
+const STREAM_AHEAD: usize = 80;
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Screens {
     Typing,
     TypingResult,
+    TypingFailed,
     GlobalResultMain,
     LetterResult,
     Exiting,
     Main,
     Alert,
+    Info,
+    Confirm,
+    Profiles,
+    ProfileName,
+    ReplayList,
+    Replay,
+    Ngrams,
+    Progress,
+    Confusions,
+}
+
+// the actions, that are done only after the user confirmed them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    RestoreBackup,
+    DeleteProfile,
+    ResetResults,
 }
 
 impl Screens {
@@ -28,37 +65,86 @@ impl Screens {
         match self {
             Screens::Typing => "Typing",
             Screens::TypingResult => "Typing Results",
+            Screens::TypingFailed => "Failed",
             Screens::GlobalResultMain => "Global Typing Results",
             Screens::LetterResult => "Global Letter Result",
             Screens::Exiting => "Exit",
             Screens::Main => "Blind Typing",
             Screens::Alert => "TODO error mssg?",
+            Screens::Info => "Info",
+            Screens::Confirm => "Confirm",
+            Screens::Profiles => "Profiles",
+            Screens::ProfileName => "Profile Name",
+            Screens::ReplayList => "Replay",
+            Screens::Replay => "Replay",
+            Screens::Ngrams => "Letter Sequences",
+            Screens::Progress => "Progress",
+            Screens::Confusions => "Confusion Matrix",
         }
     }
-    pub fn get_keys_hints(&self) -> &str {
+    pub fn get_keys_hints(&self, keys: &KeyBindings) -> String {
         match self {
-            Screens::Main => {
-                "q - exit app, s - start, r - global results, R - delete existing result data, t - get new texts"
+            Screens::Main => format!(
+                "{} - exit app, {} - start, {} - global results, {} - delete existing result data, {} - undo the last delete, {} - get new texts, {} - profiles, {} - replay, {} - letter sequences, {} - progress, {} - confusions, {} - change the mode, {} - free mode, {} - change the challenge",
+                keys.quit, keys.start, keys.results, keys.delete_results, keys.undo_reset, keys.new_texts, keys.profiles, keys.replay, keys.ngrams, keys.progress, keys.confusions, keys.mode, keys.free, keys.challenge
+            ),
+            Screens::Typing => "Esc - main screen, Tab - empty the typing, Backspace / Ctrl+Backspace - delete the letter / the word in the free mode".to_string(),
+            Screens::TypingResult => {
+                "q - main screen, c - continue typing, Shift+Tab - switch to latency".to_string()
+            }
+            Screens::TypingFailed => {
+                "r - try the same text again, c - next text, q - main screen".to_string()
             }
-            Screens::Typing => "Esc - main screen, Tab - empty the typing",
-            Screens::TypingResult => "q - main screen, c - continue typing",
             Screens::GlobalResultMain => {
-                "letter - letter result, Esc - main screen, Tab - switch to big letters"
+                "letter - letter result, Esc - main screen, Tab - switch to big letters, Shift+Tab - switch to latency".to_string()
             }
             Screens::LetterResult => {
-                "letter - another letter, Esc - global results, Tab - switch to big letters"
+                "letter - another letter, Enter - progress of the letter, Esc - global results, Tab - switch to big letters, Shift+Tab - switch to latency"
+                    .to_string()
+            }
+            Screens::Exiting => "y - yes, n - no".to_string(),
+            Screens::Alert => "TODO ".to_string(),
+            Screens::Info => "letter - close".to_string(),
+            Screens::Confirm => "y - yes, n - no".to_string(),
+            Screens::Profiles => "Up/Down - select, Enter - use the profile, n - new, r - rename, c - copy, d - delete, Esc - main screen".to_string(),
+            Screens::ProfileName => "Enter - save, Esc - cancel".to_string(),
+            Screens::ReplayList => {
+                "Up/Down - select, Enter - replay the test, g - race against the test, Esc - main screen".to_string()
+            }
+            Screens::Replay => "Space - play/pause, +/- - speed, Left/Right - seek, ,/. - step by keystroke, Esc - back".to_string(),
+            Screens::Ngrams => "2 - bigrams, 3 - trigrams, Esc - main screen".to_string(),
+            Screens::Progress => {
+                "1 - last 7 days, 2 - last 30 days, 3 - all the time, Esc - back".to_string()
+            }
+            Screens::Confusions => {
+                "s - change the sorting, Up/Down - scroll, e - export as csv, Esc - main screen".to_string()
             }
-            Screens::Exiting => "y - yes, n - no",
-            Screens::Alert => "TODO ",
         }
     }
 }
 
+// the events of the background tasks, they come to the main loop through the channel
+pub enum AppEvent {
+    // the answer of chatgpt with the new texts or the error
+    NewTexts(Result<String, String>),
+}
+
 pub struct App {
+    args: Args,
     file: Vec<String>,
+    storage: Storage,
+    config: Config,
     events: AppEvents,
     typing_mode: TypingMode,
-    rand: ThreadRng,
+    rand: StdRng,
+    profiles: ProfilesState,
+    replays: ReplayList,
+    replay: Option<Replay>,
+    ngrams: NgramsState,
+    progress: ProgressState,
+    confusions: ConfusionsState,
+    sender: UnboundedSender<AppEvent>,
+    is_fetching_texts: bool,
 }
 
 struct AppEvents {
@@ -66,7 +152,12 @@ struct AppEvents {
     previous_screen: Screens,
     pressed_letter: char,
     is_uppercase: bool,
+    // the results show the latency of the keys instead of the accuracy
+    show_latency: bool,
     alert_text: String,
+    pending_action: Option<Action>,
+    // the message of the background task, that waits for the main screen
+    pending_notice: Option<Result<String, String>>,
 }
 
 impl AppEvents {
@@ -76,13 +167,19 @@ impl AppEvents {
             previous_screen: Screens::Main,
             pressed_letter: ' ',
             is_uppercase: false,
+            show_latency: false,
             alert_text: "init value".to_string(),
+            pending_action: None,
+            pending_notice: None,
         }
     }
 
     fn change_screen_to(&mut self, new_screen: Screens) {
         if new_screen != self.current_screen {
-            if self.current_screen != Screens::Alert {
+            if !matches!(
+                self.current_screen,
+                Screens::Alert | Screens::Info | Screens::Confirm
+            ) {
                 self.previous_screen = self.current_screen;
             }
             self.current_screen = new_screen;
@@ -91,23 +188,58 @@ impl AppEvents {
 }
 
 impl App {
-    pub fn new(filename: &str) -> App {
-        let file = fs::read_to_string(filename).unwrap_or_else(|_| {
-            let sentences = get_default_sentences();
-            let mut file = File::create(filename).unwrap();
-            file.write_all(sentences.as_bytes()).unwrap();
-            sentences
-        });
+    pub fn new(
+        args: &Args,
+        config: Config,
+        storage: Storage,
+        sender: UnboundedSender<AppEvent>,
+    ) -> Result<App, io::Error> {
+        let file = read_file(args, &storage, &config)?;
 
-        App {
+        let rand = match args.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let mut app = App {
+            args: args.clone(),
+            storage,
+            config,
             events: AppEvents::new(),
             typing_mode: TypingMode::new(),
-            rand: rand::thread_rng(),
-            file: file
-                .split('\n')
-                .filter(|l| l != &"")
-                .map(|l| l.to_string())
-                .collect(),
+            rand,
+            file,
+            profiles: ProfilesState::new(),
+            replays: ReplayList::new(),
+            replay: None,
+            ngrams: NgramsState::new(),
+            progress: ProgressState::new(),
+            confusions: ConfusionsState::new(),
+            sender,
+            is_fetching_texts: false,
+        };
+        app.check_history();
+
+        Ok(app)
+    }
+
+    fn check_history(&mut self) {
+        // the broken results are not overwritten, the user can restore them from the backup
+        if let Err(err) = self.storage.results().read_history() {
+            match err.kind() {
+                ErrorKind::InvalidData => self.confirm(
+                    format!(
+                        "The results are broken: {err}\n\nDo you want to restore the last backup?"
+                    ),
+                    Action::RestoreBackup,
+                ),
+                _ => self.alert(err.to_string()),
+            }
+            return;
+        }
+        // the old results are added only to the history, that is not broken
+        if let Err(err) = self.storage.migrate_legacy_results() {
+            self.alert(format!("The old results are not migrated: {err}"));
         }
     }
 
@@ -125,6 +257,20 @@ impl App {
 
     pub fn change_screen(&mut self, new_screen: Screens) {
         self.events.change_screen_to(new_screen);
+        // the messages of the background tasks don't interrupt the typing
+        if new_screen == Screens::Main {
+            if let Some(notice) = self.events.pending_notice.take() {
+                self.notify(notice);
+            }
+        }
+    }
+
+    pub fn change_latency_mode(&mut self) {
+        self.events.show_latency = !self.events.show_latency;
+    }
+
+    pub fn get_show_latency(&self) -> bool {
+        self.events.show_latency
     }
 
     pub fn get_uppercase(&self) -> bool {
@@ -147,8 +293,16 @@ impl App {
         &self.events.current_screen
     }
 
-    pub fn get_typing_text(&self) -> Line {
-        self.typing_mode.get_text_to_render()
+    pub fn get_storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn get_typing_text(&self, width: usize) -> (Vec<Line<'_>>, usize) {
+        self.typing_mode.get_text_to_render(width)
     }
 
     pub fn get_last_results(&self) -> &JSONResults {
@@ -160,9 +314,90 @@ impl App {
         self.events.current_screen = Screens::Alert;
     }
 
-    pub fn guess(&mut self) -> Option<bool> {
-        self.typing_mode.guess(self.events.pressed_letter)
-        // blanked cursore todo!
+    // the same as the alert, but not for the errors
+    pub fn inform(&mut self, text: String) {
+        self.events.alert_text = text;
+        self.events.current_screen = Screens::Info;
+    }
+
+    pub fn confirm(&mut self, text: String, action: Action) {
+        self.events.alert_text = text;
+        self.events.pending_action = Some(action);
+        self.change_screen(Screens::Confirm);
+    }
+
+    // does the action, that the user has confirmed
+    pub fn run_pending_action(&mut self) {
+        self.change_screen(self.get_previous_screen());
+
+        let result = match self.events.pending_action.take() {
+            Some(Action::RestoreBackup) => self.storage.results().restore_backup(),
+            Some(Action::DeleteProfile) => self.delete_profile(),
+            Some(Action::ResetResults) => self.reset_results(),
+            None => Ok(()),
+        };
+        if let Err(err) = result {
+            self.alert(err.to_string());
+        }
+    }
+
+    pub fn cancel_pending_action(&mut self) {
+        self.events.pending_action = None;
+        self.change_screen(self.get_previous_screen());
+    }
+
+    pub fn backspace(&mut self) {
+        self.typing_mode.backspace();
+    }
+
+    pub fn delete_word(&mut self) {
+        self.typing_mode.delete_word();
+    }
+
+    // enter is ignored, like before the paragraphs, unless the line breaks are typed
+    pub fn guess_line_break(&mut self) {
+        if !self.config.line_breaks && !self.typing_mode.is_line_break_expected() {
+            return;
+        }
+        self.set_key_pressed('\n');
+        self.guess();
+    }
+
+    pub fn guess(&mut self) {
+        match self.typing_mode.guess(self.events.pressed_letter) {
+            // the typing is ended, so we save the results
+            Guess::Finished => {
+                self.finish_typing();
+                return;
+            }
+            Guess::Failed => {
+                self.change_screen(Screens::TypingFailed);
+                return;
+            }
+            Guess::Right | Guess::Wrong => (),
+        }
+        // the timed modes never come to the end of the text
+        if self.typing_mode.is_timed() && self.typing_mode.get_remaining_chars() < STREAM_AHEAD {
+            let text = self.get_random_text();
+            self.typing_mode.append_text(&text);
+        }
+    }
+
+    fn finish_typing(&mut self) {
+        // the test below the accuracy gate doesn't count
+        if self.typing_mode.check_gate() {
+            self.change_screen(Screens::TypingFailed);
+            return;
+        }
+        // the timed test without any typed letter has no text, that could be saved or replayed
+        if self.typing_mode.get_typed_chars() == 0 {
+            self.inform("Nothing was typed, so the test is not saved.".to_string());
+            return;
+        }
+        self.change_screen(Screens::TypingResult);
+        if let Err(err) = self.typing_mode.result_calculation(self.storage.results()) {
+            self.alert(format!("Cannot save the results: {err}"));
+        }
     }
 
     pub fn reload_typing(&mut self) {
@@ -170,21 +405,280 @@ impl App {
         self.events.is_uppercase = false;
     }
 
-    pub fn start_typing(&mut self) {
+    // the failed test starts again on the same text
+    pub fn retry_typing(&mut self) {
+        self.reload_typing();
         self.change_screen(Screens::Typing);
+    }
+
+    pub fn get_failure(&self) -> Option<&Failure> {
+        self.typing_mode.get_failure()
+    }
+
+    pub fn start_typing(&mut self) {
+        if self.file.is_empty() {
+            self.alert(format!(
+                "There are no texts to type, write them to {}",
+                self.storage.texts_path().display()
+            ));
+            return;
+        }
+        let mode = self.config.mode;
+        let mut text = match mode.get_word_count() {
+            Some(count) => self.get_random_words(count),
+            None => self.get_random_text(),
+        };
+        // the timed modes start with enough text, the next texts come while typing
+        while mode.get_time_limit_ms().is_some() && text.chars().count() < STREAM_AHEAD {
+            text.push(' ');
+            text.push_str(&self.get_random_text());
+        }
+
+        let ghost = match self.config.ghost && mode == Mode::Sentence {
+            true => self.find_best_run(&text),
+            false => None,
+        };
+        self.start_typing_text(text, mode, ghost);
+    }
+
+    fn get_random_text(&mut self) -> String {
         let index = self.rand.gen_range(0..self.file.len());
-        self.typing_mode.init(self.file[index].clone());
+        self.file[index].clone()
+    }
+
+    // the words are taken from the random texts one after another, until there are enough of them
+    fn get_random_words(&mut self, count: usize) -> String {
+        let mut words = vec![];
+        while words.len() < count {
+            let text = self.get_random_text();
+            words.extend(
+                text.split_whitespace()
+                    .take(count - words.len())
+                    .map(|word| word.to_string()),
+            );
+        }
+        words.join(" ")
+    }
+
+    // the mode of the typing is switched on the main screen, the global results show only its tests
+    pub fn change_mode(&mut self) {
+        self.config.mode = self.config.mode.get_next();
+    }
+
+    pub fn change_challenge(&mut self) {
+        self.config.challenge = self.config.challenge.get_next();
+    }
+
+    // in the free mode the wrong letters are typed into the text, it is used from the next test
+    pub fn toggle_free(&mut self) {
+        self.config.free = !self.config.free;
+    }
+
+    fn start_typing_text(&mut self, text: String, mode: Mode, ghost: Option<Ghost>) {
+        self.change_screen(Screens::Typing);
+        self.typing_mode.init(text, mode, self.config.free);
+        self.typing_mode
+            .set_challenge(self.config.challenge, self.config.gate);
+        if let Some(ghost) = ghost {
+            self.typing_mode.set_ghost(ghost);
+        }
+    }
+
+    // the fastest run on the same text, only the runs with the keystrokes can be the ghosts, it
+    // must be the sentence of the same free or strict mode, so the runs can be compared
+    fn find_best_run(&self, text: &str) -> Option<Ghost> {
+        let history = self.storage.results().read_history().ok()?;
+        history
+            .iter()
+            .filter(|record| !record.text.is_empty() && record.text == text)
+            .filter(|record| record.mode == Mode::Sentence && record.free == self.config.free)
+            .filter_map(|record| Some((record, record.keystrokes.last()?.offset_ms)))
+            .min_by_key(|(_, duration_ms)| *duration_ms)
+            .map(|(record, _)| Ghost::new(&record.keystrokes))
+    }
+
+    pub fn get_live_stats(&self) -> LiveStats {
+        self.typing_mode.get_live_stats()
+    }
+
+    pub fn get_ghost_diff(&self) -> Option<i64> {
+        self.typing_mode.get_ghost_diff()
+    }
+
+    // the screen changes without the pressed keys, while the replay or the ghost are moving and
+    // while the time of the typing is running
+    pub fn needs_frames(&self) -> bool {
+        match self.events.current_screen {
+            Screens::Replay => self
+                .replay
+                .as_ref()
+                .is_some_and(|replay| replay.is_playing()),
+            Screens::Typing => self.typing_mode.has_ghost() || self.typing_mode.is_started(),
+            _ => false,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.tick();
+        }
+        if self.events.current_screen == Screens::Typing && self.typing_mode.is_time_over() {
+            self.finish_typing();
+        }
+    }
+
+    pub fn is_fetching_texts(&self) -> bool {
+        self.is_fetching_texts
+    }
+
+    // chatgpt answers in the background, the texts are saved, when its answer comes to handle_event
+    pub fn fetch_new_texts(&mut self) {
+        if self.is_fetching_texts {
+            self.inform("The new texts are already being fetched.".to_string());
+            return;
+        }
+        let key = match self.storage.read_api_key() {
+            Ok(key) => key,
+            Err(err) => {
+                self.alert(format!(
+                    "You need to add the file with yor api key at \"{}\" if you want to use this feature.\n\n{}",
+                    self.storage.api_key_path().display(),
+                    err,
+                ));
+                return;
+            }
+        };
+
+        let prompt = self.config.prompt.clone();
+        let sender = self.sender.clone();
+        self.is_fetching_texts = true;
+        tokio::spawn(async move {
+            let texts = get_chatgpt_words(key, &prompt)
+                .await
+                .map_err(|err| err.to_string());
+            // the app can be closed before the answer comes, then nobody needs it
+            let _ = sender.send(AppEvent::NewTexts(texts));
+        });
+    }
+
+    pub fn handle_event(&mut self, event: AppEvent) {
+        match event {
+            AppEvent::NewTexts(texts) => {
+                self.is_fetching_texts = false;
+                let notice = texts
+                    .map_err(|err| format!("Cannot get the new texts: {err}"))
+                    .and_then(|texts| {
+                        self.save_new_texts(&texts)
+                            .map_err(|err| format!("Cannot save the new texts: {err}"))
+                    });
+                self.notify(notice);
+            }
+        }
+    }
+
+    fn save_new_texts(&mut self, texts: &str) -> Result<String, io::Error> {
+        self.storage.write_texts(texts)?;
+        self.file = read_file(&self.args, &self.storage, &self.config)?;
+        Ok(format!(
+            "The new texts are saved to {}",
+            self.storage.texts_path().display()
+        ))
     }
 
-    pub async fn get_new_texts(&mut self) -> Result<(), chatgpt::err::Error> {
-        let words = get_chatgpt_words().await?;
-        let mut file = File::create("texts.txt").unwrap();
-        file.write_all(words.as_bytes()).unwrap();
+    // the message is shown right away only on the main screen, otherwise it waits for it
+    fn notify(&mut self, notice: Result<String, String>) {
+        if self.events.current_screen != Screens::Main {
+            self.events.pending_notice = Some(notice);
+            return;
+        }
+        match notice {
+            Ok(text) => self.inform(text),
+            Err(text) => self.alert(text),
+        }
+    }
+
+    // the user sees, how many tests will be deleted, before they are deleted
+    pub fn ask_reset_results(&mut self) {
+        let tests = match self.storage.results().read_history() {
+            Ok(history) => history.len(),
+            Err(err) => {
+                self.alert(format!("Cannot read the results: {err}"));
+                return;
+            }
+        };
+        if tests == 0 {
+            self.alert("There are no results to delete.".to_string());
+            return;
+        }
+
+        self.confirm(
+            format!(
+                "Do you want to delete all {tests} tests of the profile \"{}\" from {}?\n\nThe backup will be saved, you can undo it with {} on the main screen.",
+                self.storage.profile(),
+                self.storage.results().path().display(),
+                self.config.keys.undo_reset,
+            ),
+            Action::ResetResults,
+        );
+    }
+
+    fn reset_results(&mut self) -> Result<(), io::Error> {
+        let backup = self.storage.reset_results()?;
+        self.inform(format!(
+            "The results were deleted, the backup is saved to {}",
+            backup.display()
+        ));
         Ok(())
     }
 
-    pub fn delete_json(&self) {
-        let mut file = File::create("results.json").unwrap();
-        file.write_all("".as_bytes()).unwrap();
+    pub fn undo_reset(&mut self) {
+        match self.storage.undo_reset() {
+            Ok(tests) => self.inform(format!("{tests} tests were restored.")),
+            Err(err) => self.alert(format!("Cannot undo the reset: {err}")),
+        }
     }
 }
+
+// the texts of the profile, the text from the command line replaces all of them
+// the texts without any letters are not used, there must be at least one text to type
+fn read_file(args: &Args, storage: &Storage, config: &Config) -> Result<Vec<String>, io::Error> {
+    let file = match &args.text {
+        Some(text) => text.clone(),
+        None => storage.read_texts()?,
+    };
+    let texts = match config.paragraphs {
+        true => get_paragraphs(&file, config.line_breaks),
+        false => file
+            .split('\n')
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.to_string())
+            .collect(),
+    };
+
+    if texts.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "There are no texts to type in {}",
+                storage.texts_path().display()
+            ),
+        ));
+    }
+    Ok(texts)
+}
+
+// the paragraphs are separated with the empty lines, their lines are joined with the line
+// breaks, if they must be typed
+fn get_paragraphs(file: &str, line_breaks: bool) -> Vec<String> {
+    let separator = match line_breaks {
+        true => "\n",
+        false => " ",
+    };
+    file.split('\n')
+        .map(str::trim_end)
+        .collect::<Vec<&str>>()
+        .split(|line| line.trim().is_empty())
+        .filter(|lines| !lines.is_empty())
+        .map(|lines| lines.join(separator))
+        .collect()
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use super::{typing_screen::JSONResults, App, Screens};
+
+// how many of the most often confused pairs are highlighted
+pub const TOP_CONFUSIONS: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConfusionSort {
+    // the letters with the most wrong presses at first
+    Mistypes,
+    Alphabet,
+}
+
+impl ConfusionSort {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ConfusionSort::Mistypes => "by the mistypes",
+            ConfusionSort::Alphabet => "by the alphabet",
+        }
+    }
+}
+
+// how many times every key was pressed, when a letter was expected
+pub struct ConfusionMatrix {
+    // the expected letters, the rows
+    pub expected: Vec<char>,
+    // the pressed keys, the columns
+    pub pressed: Vec<char>,
+    counts: HashMap<(char, char), usize>,
+}
+
+impl ConfusionMatrix {
+    pub fn new(results: &JSONResults) -> ConfusionMatrix {
+        let mut counts = HashMap::new();
+        for (expected, info) in results.letters_info.iter() {
+            for (pressed, amount) in info.letter_accuracies.iter() {
+                counts.insert((*expected, *pressed), *amount);
+            }
+        }
+
+        let mut expected = results.letters_info.keys().copied().collect::<Vec<char>>();
+        let mut pressed = counts
+            .keys()
+            .map(|(_, pressed)| *pressed)
+            .collect::<Vec<char>>();
+        expected.sort();
+        pressed.sort();
+        pressed.dedup();
+
+        ConfusionMatrix {
+            expected,
+            pressed,
+            counts,
+        }
+    }
+
+    pub fn get(&self, expected: char, pressed: char) -> usize {
+        *self.counts.get(&(expected, pressed)).unwrap_or(&0)
+    }
+
+    // the wrong presses, when the letter was expected
+    fn get_row_mistypes(&self, expected: char) -> usize {
+        self.pressed
+            .iter()
+            .filter(|pressed| **pressed != expected)
+            .map(|pressed| self.get(expected, *pressed))
+            .sum()
+    }
+
+    // the presses of the key, when another letter was expected
+    fn get_column_mistypes(&self, pressed: char) -> usize {
+        self.expected
+            .iter()
+            .filter(|expected| **expected != pressed)
+            .map(|expected| self.get(*expected, pressed))
+            .sum()
+    }
+
+    // only the letters and keys with the mistypes are interesting, the best at the end
+    pub fn sort(&mut self, sort: ConfusionSort) {
+        self.expected.sort();
+        self.pressed.sort();
+        if sort == ConfusionSort::Mistypes {
+            let mut expected = std::mem::take(&mut self.expected);
+            let mut pressed = std::mem::take(&mut self.pressed);
+            expected.sort_by_key(|ch| std::cmp::Reverse(self.get_row_mistypes(*ch)));
+            pressed.sort_by_key(|ch| std::cmp::Reverse(self.get_column_mistypes(*ch)));
+            (self.expected, self.pressed) = (expected, pressed);
+        }
+    }
+
+    pub fn get_mistyped_letters(&self) -> Vec<char> {
+        self.expected
+            .iter()
+            .copied()
+            .filter(|ch| self.get_row_mistypes(*ch) > 0)
+            .collect()
+    }
+
+    pub fn get_mistyped_keys(&self) -> Vec<char> {
+        self.pressed
+            .iter()
+            .copied()
+            .filter(|ch| self.get_column_mistypes(*ch) > 0)
+            .collect()
+    }
+
+    // the pairs of the expected letter and the pressed key, that were confused most often
+    pub fn get_top_confusions(&self, amount: usize) -> Vec<(char, char, usize)> {
+        let mut confusions = self
+            .counts
+            .iter()
+            .filter(|((expected, pressed), _)| expected != pressed)
+            .map(|((expected, pressed), count)| (*expected, *pressed, *count))
+            .collect::<Vec<(char, char, usize)>>();
+        confusions.sort_by(|a, b| b.2.cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+        confusions.truncate(amount);
+        confusions
+    }
+
+    // the whole matrix with the right presses, the rows are the expected letters
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("expected");
+        for pressed in self.pressed.iter() {
+            csv.push(',');
+            csv.push_str(&to_csv_field(*pressed));
+        }
+        csv.push('\n');
+
+        for expected in self.expected.iter() {
+            csv.push_str(&to_csv_field(*expected));
+            for pressed in self.pressed.iter() {
+                csv.push_str(&format!(",{}", self.get(*expected, *pressed)));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+// the space, the comma and the quote must be quoted, otherwise they are lost in the csv
+fn to_csv_field(ch: char) -> String {
+    match ch {
+        '"' => "\"\"\"\"".to_string(),
+        ',' => "\",\"".to_string(),
+        ch if ch.is_whitespace() => format!("\"{ch}\""),
+        ch => ch.to_string(),
+    }
+}
+
+pub struct ConfusionsState {
+    pub matrix: Option<ConfusionMatrix>,
+    pub sort: ConfusionSort,
+    // the first shown row, if the matrix doesn't fit on the screen
+    pub scroll: usize,
+}
+
+impl ConfusionsState {
+    pub fn new() -> ConfusionsState {
+        ConfusionsState {
+            matrix: None,
+            sort: ConfusionSort::Mistypes,
+            scroll: 0,
+        }
+    }
+}
+
+impl App {
+    pub fn get_confusions(&self) -> &ConfusionsState {
+        &self.confusions
+    }
+
+    pub fn open_confusions(&mut self) {
+        let results = match self
+            .storage
+            .results()
+            .read_results(self.config.weighting, self.config.mode)
+        {
+            Ok(results) => results,
+            Err(err) => {
+                self.alert(format!("Cannot read the results: {err}"));
+                return;
+            }
+        };
+
+        let mut matrix = ConfusionMatrix::new(&results);
+        matrix.sort(self.confusions.sort);
+        self.confusions.matrix = Some(matrix);
+        self.confusions.scroll = 0;
+        self.change_screen(Screens::Confusions);
+    }
+
+    pub fn change_confusions_sort(&mut self) {
+        self.confusions.sort = match self.confusions.sort {
+            ConfusionSort::Mistypes => ConfusionSort::Alphabet,
+            ConfusionSort::Alphabet => ConfusionSort::Mistypes,
+        };
+        if let Some(matrix) = self.confusions.matrix.as_mut() {
+            matrix.sort(self.confusions.sort);
+        }
+    }
+
+    pub fn scroll_confusions_down(&mut self) {
+        let rows = self
+            .confusions
+            .matrix
+            .as_ref()
+            .map_or(0, |matrix| matrix.get_mistyped_letters().len());
+        if self.confusions.scroll + 1 < rows {
+            self.confusions.scroll += 1;
+        }
+    }
+
+    pub fn scroll_confusions_up(&mut self) {
+        self.confusions.scroll = self.confusions.scroll.saturating_sub(1);
+    }
+
+    pub fn export_confusions(&mut self) {
+        let Some(matrix) = self.confusions.matrix.as_ref() else {
+            return;
+        };
+        match self
+            .storage
+            .write_export("confusions", "csv", &matrix.to_csv())
+        {
+            Ok(path) => self.inform(format!(
+                "The confusion matrix is saved to {}",
+                path.display()
+            )),
+            Err(err) => self.alert(format!("Cannot save the confusion matrix: {err}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_has_the_expected_letters_as_rows() {
+        let results: JSONResults = serde_json::from_value(serde_json::json!({
+            "wpm": 0.0,
+            "total_accuracy": 0.0,
+            "letters_info": {
+                "a": {"main_letter": "a", "letter_accuracies": {"a": 5, "s": 2}, "presses_of_key": 7},
+                ",": {"main_letter": ",", "letter_accuracies": {",": 1, " ": 1}, "presses_of_key": 2},
+            },
+        }))
+        .unwrap();
+
+        let matrix = ConfusionMatrix::new(&results);
+
+        assert_eq!(
+            matrix.to_csv(),
+            "expected,\" \",\",\",a,s\n\",\",1,1,0,0\na,0,0,5,2\n"
+        );
+        assert_eq!(matrix.get_top_confusions(1), [('a', 's', 2)]);
+    }
+}
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{cli::Mode, config::Weighting, storage::migrations::SCHEMA_VERSION};
+
+use super::typing_screen::JSONResults;
+
+// one finished typing test, every test is stored as one line in the history file
+#[derive(Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub schema_version: u32,
+    pub timestamp: DateTime<Local>,
+    pub text: String,
+    pub mode: Mode,
+    // the amount of the typed characters and of all the pressed keys
+    #[serde(default)]
+    pub chars: usize,
+    #[serde(default)]
+    pub presses: usize,
+    #[serde(default)]
+    pub duration_ms: i64,
+    // every pressed key of the test, the older tests have no keystrokes
+    #[serde(default)]
+    pub keystrokes: Vec<Keystroke>,
+    // the wrong letters were typed into the text and fixed with backspace
+    #[serde(default)]
+    pub free: bool,
+    // wpm, accuracy and the info about every letter of this test
+    #[serde(flatten)]
+    pub results: JSONResults,
+}
+
+// the pressed keys of the free mode, that delete the typed letters, are stored as these characters
+pub const BACKSPACE: char = '\u{8}';
+pub const DELETE_WORD: char = '\u{17}';
+
+// one pressed key while typing
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Keystroke {
+    pub expected: char,
+    pub pressed: char,
+    // the time since the first pressed key of the test
+    pub offset_ms: i64,
+    // the key has typed the next letter of the text, in the free mode only the first time
+    pub correct: bool,
+}
+
+impl Keystroke {
+    pub fn is_correction(&self) -> bool {
+        self.pressed == BACKSPACE || self.pressed == DELETE_WORD
+    }
+}
+
+impl SessionRecord {
+    pub fn new(
+        text: String,
+        mode: Mode,
+        results: JSONResults,
+        duration_ms: i64,
+        keystrokes: Vec<Keystroke>,
+    ) -> SessionRecord {
+        SessionRecord {
+            schema_version: SCHEMA_VERSION,
+            timestamp: Local::now(),
+            chars: text.chars().count(),
+            text,
+            mode,
+            presses: keystrokes
+                .iter()
+                .filter(|keystroke| !keystroke.is_correction())
+                .count(),
+            duration_ms,
+            keystrokes,
+            free: false,
+            results,
+        }
+    }
+}
+
+// the global results of all the tests in the history
+pub fn get_global_results(history: &[SessionRecord], weighting: Weighting) -> Option<JSONResults> {
+    if history.is_empty() {
+        return None;
+    }
+
+    let mut global = JSONResults::new();
+    let (mut chars, mut presses) = (0, 0);
+    // wpm, raw wpm, net wpm and cpm multiplied with the weight of their test
+    let (mut weighted, mut weights) = ([0.0; 4], 0.0);
+
+    for session in history {
+        global.update(&session.results);
+        global.corrections += session.results.corrections;
+        chars += session.chars;
+        presses += session.presses;
+
+        // weighted by the time it is the same as all the typed words divided by all the time
+        let weight = match weighting {
+            Weighting::Time => session.duration_ms as f64,
+            Weighting::Length => session.chars as f64,
+        };
+        let results = &session.results;
+        for (sum, speed) in
+            weighted
+                .iter_mut()
+                .zip([results.wpm, results.raw_wpm, results.net_wpm, results.cpm])
+        {
+            *sum += speed * weight;
+        }
+        weights += weight;
+    }
+
+    if weights > 0.0 {
+        let [wpm, raw_wpm, net_wpm, cpm] =
+            weighted.map(|sum| (sum / weights * 10.0).round() / 10.0);
+        (global.wpm, global.raw_wpm, global.net_wpm, global.cpm) = (wpm, raw_wpm, net_wpm, cpm);
+    }
+    global.total_accuracy = get_accuracy(chars, presses);
+
+    Some(global)
+}
+
+pub fn get_accuracy(right: usize, all: usize) -> f64 {
+    if all == 0 {
+        return 0.0;
+    }
+    ((right as f64 / all as f64) * 1000.0).round() / 10.0
+}
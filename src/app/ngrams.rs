@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use super::{
+    history::{get_accuracy, Keystroke, SessionRecord},
+    App, Screens,
+};
+
+// the sequences, that were typed less times, are not shown, one slow try says nothing
+pub const MIN_OCCURRENCES: usize = 3;
+
+// the sequence of the letters, like "th" or "ing", in all the tests
+#[derive(Clone, PartialEq, Debug)]
+pub struct NgramInfo {
+    pub ngram: String,
+    pub occurrences: usize,
+    // how many times any letter after the first one was mistyped
+    pub errors: usize,
+    // the time from the first to the last letter of all the occurrences
+    transitions_ms: i64,
+}
+
+impl NgramInfo {
+    fn new(ngram: String) -> NgramInfo {
+        NgramInfo {
+            ngram,
+            occurrences: 0,
+            errors: 0,
+            transitions_ms: 0,
+        }
+    }
+
+    pub fn get_error_rate(&self) -> f64 {
+        100.0 - get_accuracy(self.occurrences - self.errors, self.occurrences)
+    }
+
+    pub fn get_average_transition(&self) -> f64 {
+        (self.transitions_ms as f64 / self.occurrences as f64).round()
+    }
+}
+
+// the right typed letter of the test
+struct TypedLetter {
+    ch: char,
+    offset_ms: i64,
+    // if the wrong key was pressed before the right one
+    is_mistyped: bool,
+}
+
+// the statistics of all the sequences of n letters inside the words, they are calculated from the
+// keystrokes, so the older tests without them are not counted
+pub fn get_ngrams(history: &[SessionRecord], n: usize) -> Vec<NgramInfo> {
+    let mut ngrams: HashMap<String, NgramInfo> = HashMap::new();
+
+    for record in history {
+        for letters in get_typed_letters(&record.keystrokes).windows(n) {
+            if letters.iter().any(|letter| letter.ch.is_whitespace()) {
+                continue;
+            }
+            let ngram = letters.iter().map(|letter| letter.ch).collect::<String>();
+            let info = ngrams
+                .entry(ngram.clone())
+                .or_insert_with(|| NgramInfo::new(ngram));
+
+            info.occurrences += 1;
+            // the first letter belongs to the transition before the sequence
+            if letters[1..].iter().any(|letter| letter.is_mistyped) {
+                info.errors += 1;
+            }
+            info.transitions_ms += letters[n - 1].offset_ms - letters[0].offset_ms;
+        }
+    }
+
+    ngrams.into_values().collect()
+}
+
+fn get_typed_letters(keystrokes: &[Keystroke]) -> Vec<TypedLetter> {
+    let mut letters = vec![];
+    let mut is_mistyped = false;
+    for keystroke in keystrokes {
+        if keystroke.correct {
+            letters.push(TypedLetter {
+                ch: keystroke.expected,
+                offset_ms: keystroke.offset_ms,
+                is_mistyped,
+            });
+            is_mistyped = false;
+        } else {
+            is_mistyped = true;
+        }
+    }
+    letters
+}
+
+pub struct NgramsState {
+    // 2 for the bigrams, 3 for the trigrams
+    pub size: usize,
+    pub slowest: Vec<NgramInfo>,
+    pub most_errors: Vec<NgramInfo>,
+}
+
+impl NgramsState {
+    pub fn new() -> NgramsState {
+        NgramsState {
+            size: 2,
+            slowest: vec![],
+            most_errors: vec![],
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self.size {
+            2 => "bigrams",
+            _ => "trigrams",
+        }
+    }
+}
+
+impl App {
+    pub fn get_ngrams(&self) -> &NgramsState {
+        &self.ngrams
+    }
+
+    pub fn open_ngrams(&mut self) {
+        if self.load_ngrams(self.ngrams.size) {
+            self.change_screen(Screens::Ngrams);
+        }
+    }
+
+    pub fn show_ngrams(&mut self, size: usize) {
+        self.load_ngrams(size);
+    }
+
+    // the slowest and the most mistyped sequences of the history, false if they can't be read
+    fn load_ngrams(&mut self, size: usize) -> bool {
+        let history = match self.storage.results().read_history() {
+            Ok(history) => history,
+            Err(err) => {
+                self.alert(format!("Cannot read the results: {err}"));
+                return false;
+            }
+        };
+
+        let mut ngrams = get_ngrams(&history, size)
+            .into_iter()
+            .filter(|info| info.occurrences >= MIN_OCCURRENCES)
+            .collect::<Vec<NgramInfo>>();
+
+        ngrams.sort_by(|a, b| {
+            b.get_average_transition()
+                .total_cmp(&a.get_average_transition())
+                .then(a.ngram.cmp(&b.ngram))
+        });
+        self.ngrams.slowest = ngrams.clone();
+
+        ngrams.retain(|info| info.errors > 0);
+        ngrams.sort_by(|a, b| {
+            b.get_error_rate()
+                .total_cmp(&a.get_error_rate())
+                .then(b.occurrences.cmp(&a.occurrences))
+                .then(a.ngram.cmp(&b.ngram))
+        });
+        self.ngrams.most_errors = ngrams;
+        self.ngrams.size = size;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystroke(expected: char, pressed: char, offset_ms: i64) -> Keystroke {
+        Keystroke {
+            expected,
+            pressed,
+            offset_ms,
+            correct: expected == pressed,
+        }
+    }
+
+    #[test]
+    fn ngrams_are_counted_inside_the_words() {
+        let mut record: SessionRecord = serde_json::from_value(serde_json::json!({
+            "schema_version": 4,
+            "timestamp": "2024-04-10T12:00:00+02:00",
+            "text": "the th",
+            "mode": "sentence",
+            "wpm": 0.0,
+            "total_accuracy": 0.0,
+            "letters_info": {},
+        }))
+        .unwrap();
+        record.keystrokes = vec![
+            keystroke('t', 't', 0),
+            keystroke('h', 'h', 100),
+            keystroke('e', 'e', 200),
+            keystroke(' ', ' ', 300),
+            keystroke('t', 't', 400),
+            keystroke('h', 'g', 500),
+            keystroke('h', 'h', 700),
+        ];
+
+        let mut bigrams = get_ngrams(&[record], 2);
+        bigrams.sort_by(|a, b| a.ngram.cmp(&b.ngram));
+
+        assert_eq!(
+            bigrams
+                .iter()
+                .map(|info| (info.ngram.as_str(), info.occurrences, info.errors))
+                .collect::<Vec<_>>(),
+            [("he", 1, 0), ("th", 2, 1)]
+        );
+        assert_eq!(bigrams[1].get_average_transition(), 200.0);
+        assert_eq!(bigrams[1].get_error_rate(), 50.0);
+    }
+}
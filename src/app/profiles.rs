@@ -0,0 +1,199 @@
+use std::io::{self, ErrorKind};
+
+use crate::{
+    config::Config,
+    storage::{profiles, Storage},
+};
+
+use super::{read_file, Action, App, Screens};
+
+// what is done with the name, that the user has typed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NameAction {
+    New,
+    Rename,
+    Copy,
+}
+
+impl NameAction {
+    pub fn as_str(&self) -> &str {
+        match self {
+            NameAction::New => "New profile",
+            NameAction::Rename => "Rename the profile",
+            NameAction::Copy => "Copy the profile",
+        }
+    }
+}
+
+pub struct ProfilesState {
+    pub names: Vec<String>,
+    pub selected: usize,
+    pub name_input: String,
+    pub name_action: NameAction,
+}
+
+impl ProfilesState {
+    pub fn new() -> ProfilesState {
+        ProfilesState {
+            names: vec![],
+            selected: 0,
+            name_input: String::new(),
+            name_action: NameAction::New,
+        }
+    }
+
+    pub fn get_selected(&self) -> Option<&str> {
+        self.names.get(self.selected).map(|name| name.as_str())
+    }
+}
+
+impl App {
+    pub fn get_profiles(&self) -> &ProfilesState {
+        &self.profiles
+    }
+
+    pub fn open_profiles(&mut self) {
+        let current = self.storage.profile().to_string();
+        match self.reload_profiles(&current) {
+            Ok(()) => self.change_screen(Screens::Profiles),
+            Err(err) => self.alert(format!("Cannot read the profiles: {err}")),
+        }
+    }
+
+    pub fn select_next_profile(&mut self) {
+        if self.profiles.selected + 1 < self.profiles.names.len() {
+            self.profiles.selected += 1;
+        }
+    }
+
+    pub fn select_previous_profile(&mut self) {
+        self.profiles.selected = self.profiles.selected.saturating_sub(1);
+    }
+
+    // the results, the texts and the settings of the selected profile are used from now on
+    pub fn choose_profile(&mut self) {
+        let Some(name) = self.profiles.get_selected().map(|name| name.to_string()) else {
+            return;
+        };
+        match self.switch_profile(&name) {
+            Ok(()) => {
+                self.change_screen(Screens::Main);
+                self.check_history();
+            }
+            Err(err) => self.alert(format!("Cannot switch to the profile \"{name}\": {err}")),
+        }
+    }
+
+    pub fn start_name_input(&mut self, action: NameAction) {
+        let selected = self.profiles.get_selected().unwrap_or_default();
+        self.profiles.name_input = match action {
+            NameAction::New => String::new(),
+            NameAction::Rename => selected.to_string(),
+            NameAction::Copy => format!("{selected}-copy"),
+        };
+        self.profiles.name_action = action;
+        self.change_screen(Screens::ProfileName);
+    }
+
+    pub fn push_name_char(&mut self, ch: char) {
+        self.profiles.name_input.push(ch);
+    }
+
+    pub fn pop_name_char(&mut self) {
+        self.profiles.name_input.pop();
+    }
+
+    pub fn submit_name(&mut self) {
+        let name = self.profiles.name_input.trim().to_string();
+        let selected = self.profiles.get_selected().unwrap_or_default().to_string();
+
+        let result = match self.profiles.name_action {
+            NameAction::New => profiles::create_profile(&name),
+            NameAction::Rename => self.rename_profile(&selected, &name),
+            NameAction::Copy => profiles::copy_profile(&selected, &name),
+        };
+
+        self.change_screen(Screens::Profiles);
+        match result.and_then(|_| self.reload_profiles(&name)) {
+            Ok(()) => (),
+            Err(err) => self.alert(err.to_string()),
+        }
+    }
+
+    // the current profile can't be deleted, because its files are in use
+    pub fn delete_selected_profile(&mut self) {
+        let Some(name) = self.profiles.get_selected() else {
+            return;
+        };
+        if name == self.storage.profile() {
+            self.alert(
+                "The current profile cannot be deleted, switch to another profile at first."
+                    .to_string(),
+            );
+            return;
+        }
+        self.confirm(
+            format!("Do you want to delete the profile \"{name}\" with all its results, texts and settings?"),
+            Action::DeleteProfile,
+        );
+    }
+
+    pub(super) fn delete_profile(&mut self) -> Result<(), io::Error> {
+        let Some(name) = self.profiles.get_selected().map(|name| name.to_string()) else {
+            return Ok(());
+        };
+        profiles::delete_profile(&name)?;
+        let current = self.storage.profile().to_string();
+        self.reload_profiles(&current)
+    }
+
+    // the current profile is renamed together with its files, so the storage is opened again
+    fn rename_profile(&mut self, from: &str, to: &str) -> Result<(), io::Error> {
+        profiles::rename_profile(from, to)?;
+        // the files of the command line still belong to the profile of the start
+        if from == self.args.profile {
+            self.args.profile = to.to_string();
+        }
+        if from == self.storage.profile() {
+            self.switch_profile(to)?;
+        }
+        Ok(())
+    }
+
+    // the texts and the results of the command line are used only by the profile of the start,
+    // the other profiles always use their own files
+    fn switch_profile(&mut self, name: &str) -> Result<(), io::Error> {
+        let mut args = self.args.clone();
+        if name != args.profile {
+            args.texts = None;
+            args.results = None;
+        }
+
+        let mut config = Config::load(name)
+            .map_err(|err| io::Error::new(ErrorKind::InvalidData, err.to_string()))?;
+        config.apply_args(&args);
+        let storage = Storage::new(
+            name,
+            config.texts.clone(),
+            args.results.clone(),
+            config.backend,
+        )?;
+
+        self.file = read_file(&args, &storage, &config)?;
+        self.storage = storage;
+        self.config = config;
+        Ok(())
+    }
+
+    // the list of the profiles, the profile with the name is selected
+    fn reload_profiles(&mut self, name: &str) -> Result<(), io::Error> {
+        self.profiles.names = profiles::list_profiles()?;
+        self.profiles.selected = self
+            .profiles
+            .names
+            .iter()
+            .position(|profile| profile == name)
+            .unwrap_or_default();
+        Ok(())
+    }
+}
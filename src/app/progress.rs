@@ -0,0 +1,186 @@
+use chrono::{DateTime, Days, Local};
+
+use super::{history::SessionRecord, App, Screens};
+
+// how many tests are averaged for the line of the trend
+pub const MOVING_AVERAGE: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProgressRange {
+    Week,
+    Month,
+    All,
+}
+
+impl ProgressRange {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ProgressRange::Week => "the last 7 days",
+            ProgressRange::Month => "the last 30 days",
+            ProgressRange::All => "all the time",
+        }
+    }
+
+    fn get_start(&self) -> Option<DateTime<Local>> {
+        match self {
+            ProgressRange::Week => Local::now().checked_sub_days(Days::new(7)),
+            ProgressRange::Month => Local::now().checked_sub_days(Days::new(30)),
+            ProgressRange::All => None,
+        }
+    }
+}
+
+// one value of every test, the x is the number of the test in the range
+pub struct Series {
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Series {
+    fn new(name: &'static str, unit: &'static str) -> Series {
+        Series {
+            name,
+            unit,
+            points: vec![],
+        }
+    }
+
+    pub fn get_moving_average(&self) -> Vec<(f64, f64)> {
+        get_moving_average(&self.points, MOVING_AVERAGE)
+    }
+
+    // the average at the start and at the end of the range, to see if the user gets better
+    pub fn get_change(&self) -> Option<(f64, f64)> {
+        let average = self.get_moving_average();
+        Some((average.first()?.1, average.last()?.1))
+    }
+}
+
+pub struct ProgressState {
+    pub range: ProgressRange,
+    // the trend of this letter instead of the trend of the whole tests
+    pub letter: Option<char>,
+    // the times of the first and the last test in the range
+    pub period: Option<(DateTime<Local>, DateTime<Local>)>,
+    pub series: Vec<Series>,
+}
+
+impl ProgressState {
+    pub fn new() -> ProgressState {
+        ProgressState {
+            range: ProgressRange::Month,
+            letter: None,
+            period: None,
+            series: vec![],
+        }
+    }
+}
+
+// every point is the average of the last values of the window, the first points average less
+pub fn get_moving_average(points: &[(f64, f64)], window: usize) -> Vec<(f64, f64)> {
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, (x, _))| {
+            let values = &points[(i + 1).saturating_sub(window)..=i];
+            let sum: f64 = values.iter().map(|(_, y)| y).sum();
+            (*x, (sum / values.len() as f64 * 10.0).round() / 10.0)
+        })
+        .collect()
+}
+
+// wpm and accuracy of every test, or the accuracy and the latency of the letter in every test,
+// where it was typed
+fn get_series(history: &[SessionRecord], letter: Option<char>) -> Vec<Series> {
+    let Some(ch) = letter else {
+        let mut wpm = Series::new("Speed", "wpm");
+        let mut accuracy = Series::new("Accuracy", "%");
+        for (i, record) in history.iter().enumerate() {
+            wpm.points.push((i as f64, record.results.wpm));
+            accuracy
+                .points
+                .push((i as f64, record.results.total_accuracy));
+        }
+        return vec![wpm, accuracy];
+    };
+
+    let mut accuracy = Series::new("Accuracy", "%");
+    let mut latency = Series::new("Latency", "ms");
+    for (i, info) in history
+        .iter()
+        .filter_map(|record| record.results.letters_info.get(&ch))
+        .enumerate()
+    {
+        accuracy.points.push((i as f64, info.get_perc(ch)));
+        if let Some(average) = info.get_average_latency() {
+            latency.points.push((i as f64, average));
+        }
+    }
+    vec![accuracy, latency]
+}
+
+impl App {
+    pub fn get_progress(&self) -> &ProgressState {
+        &self.progress
+    }
+
+    pub fn open_progress(&mut self, letter: Option<char>) {
+        self.progress.letter = letter;
+        if self.load_progress(self.progress.range) {
+            self.change_screen(Screens::Progress);
+        }
+    }
+
+    // the letter screen shows the letter of the trend again, the keys of the ranges replaced it
+    pub fn close_progress(&mut self) {
+        if let Some(letter) = self.progress.letter {
+            self.set_key_pressed(letter);
+        }
+        self.change_screen(self.get_previous_screen());
+    }
+
+    pub fn set_progress_range(&mut self, range: ProgressRange) {
+        self.load_progress(range);
+    }
+
+    // the tests of the range, false if they can't be read
+    fn load_progress(&mut self, range: ProgressRange) -> bool {
+        let mut history = match self.storage.results().read_history() {
+            Ok(history) => history,
+            Err(err) => {
+                self.alert(format!("Cannot read the results: {err}"));
+                return false;
+            }
+        };
+        // the speeds of the different modes can't be compared, so only the current mode is shown
+        let mode = self.config.mode;
+        history.retain(|record| record.mode == mode);
+        if let Some(start) = range.get_start() {
+            history.retain(|record| record.timestamp >= start);
+        }
+
+        self.progress.range = range;
+        self.progress.period = history
+            .first()
+            .zip(history.last())
+            .map(|(first, last)| (first.timestamp, last.timestamp));
+        self.progress.series = get_series(&history, self.progress.letter);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_average_uses_the_last_values() {
+        let points = [(0.0, 10.0), (1.0, 20.0), (2.0, 30.0), (3.0, 50.0)];
+
+        assert_eq!(
+            get_moving_average(&points, 2),
+            [(0.0, 10.0), (1.0, 15.0), (2.0, 25.0), (3.0, 40.0)]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use std::time::Instant;
+
+use chrono::{DateTime, Local};
+use ratatui::text::Line;
+
+use crate::cli::Mode;
+
+use super::{
+    history::{Keystroke, BACKSPACE, DELETE_WORD},
+    typing_screen::{Ghost, TypingMode},
+    App, Screens,
+};
+
+const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+// how far the replay jumps with the arrows
+const SEEK_MS: i64 = 1000;
+
+// the test from the history, that can be replayed
+pub struct ReplayItem {
+    pub timestamp: DateTime<Local>,
+    pub text: String,
+    pub wpm: f64,
+    pub total_accuracy: f64,
+    pub free: bool,
+    keystrokes: Vec<Keystroke>,
+}
+
+pub struct ReplayList {
+    pub items: Vec<ReplayItem>,
+    pub selected: usize,
+}
+
+impl ReplayList {
+    pub fn new() -> ReplayList {
+        ReplayList {
+            items: vec![],
+            selected: 0,
+        }
+    }
+}
+
+// plays the keystrokes of the test again, the typing mode gets them as if they were typed now
+pub struct Replay {
+    text: String,
+    free: bool,
+    keystrokes: Vec<Keystroke>,
+    typing_mode: TypingMode,
+    // how many keystrokes are already played
+    position: usize,
+    // the time of the test, that is already played
+    elapsed_ms: f64,
+    speed_i: usize,
+    is_playing: bool,
+    last_tick: Instant,
+}
+
+impl Replay {
+    fn new(item: &ReplayItem) -> Replay {
+        let mut typing_mode = TypingMode::new();
+        typing_mode.init(item.text.clone(), Mode::Sentence, item.free);
+        Replay {
+            text: item.text.clone(),
+            free: item.free,
+            keystrokes: item.keystrokes.clone(),
+            typing_mode,
+            position: 0,
+            elapsed_ms: 0.0,
+            speed_i: 2,
+            is_playing: true,
+            last_tick: Instant::now(),
+        }
+    }
+
+    // moves the replay forward by the time, that has passed since the last tick
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if self.is_playing {
+            self.elapsed_ms +=
+                now.duration_since(self.last_tick).as_secs_f64() * 1000.0 * self.get_speed();
+            while self.position < self.keystrokes.len()
+                && self.keystrokes[self.position].offset_ms as f64 <= self.elapsed_ms
+            {
+                self.play_next();
+            }
+            if self.position == self.keystrokes.len() {
+                self.is_playing = false;
+            }
+        }
+        self.last_tick = now;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        // the finished replay starts again
+        if !self.is_playing && self.position == self.keystrokes.len() {
+            self.go_to(0);
+        }
+        self.is_playing = !self.is_playing;
+        self.last_tick = Instant::now();
+    }
+
+    pub fn faster(&mut self) {
+        self.speed_i = (self.speed_i + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed_i = self.speed_i.saturating_sub(1);
+    }
+
+    // jumps by the time of the test, the negative time jumps back
+    pub fn seek(&mut self, delta_ms: i64) {
+        let elapsed = (self.elapsed_ms as i64 + delta_ms).clamp(0, self.get_duration_ms());
+        let position = self
+            .keystrokes
+            .iter()
+            .take_while(|keystroke| keystroke.offset_ms <= elapsed)
+            .count();
+        self.go_to(position);
+        self.elapsed_ms = elapsed as f64;
+    }
+
+    pub fn seek_forward(&mut self) {
+        self.seek(SEEK_MS);
+    }
+
+    pub fn seek_back(&mut self) {
+        self.seek(-SEEK_MS);
+    }
+
+    // the keystroke by keystroke stepping pauses the replay
+    pub fn step_forward(&mut self) {
+        self.is_playing = false;
+        self.go_to((self.position + 1).min(self.keystrokes.len()));
+    }
+
+    pub fn step_back(&mut self) {
+        self.is_playing = false;
+        self.go_to(self.position.saturating_sub(1));
+    }
+
+    pub fn get_text_to_render(&self, width: usize) -> (Vec<Line<'_>>, usize) {
+        self.typing_mode.get_text_to_render(width)
+    }
+
+    // the last played keystroke, it is shown on the keyboard
+    pub fn get_last_keystroke(&self) -> Option<&Keystroke> {
+        self.position
+            .checked_sub(1)
+            .map(|position| &self.keystrokes[position])
+    }
+
+    // how long the user has waited before the last played keystroke
+    pub fn get_last_pause_ms(&self) -> Option<i64> {
+        let last = self.get_last_keystroke()?;
+        let before = match self.position {
+            1 => 0,
+            position => self.keystrokes[position - 2].offset_ms,
+        };
+        Some(last.offset_ms - before)
+    }
+
+    // the pauses, that are much longer than this, are the hesitations
+    pub fn get_average_pause_ms(&self) -> i64 {
+        match self.keystrokes.len() {
+            0 | 1 => 0,
+            len => self.get_duration_ms() / (len as i64 - 1),
+        }
+    }
+
+    pub fn get_speed(&self) -> f64 {
+        SPEEDS[self.speed_i]
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    pub fn get_position(&self) -> (usize, usize) {
+        (self.position, self.keystrokes.len())
+    }
+
+    pub fn get_elapsed_ms(&self) -> i64 {
+        self.elapsed_ms as i64
+    }
+
+    pub fn get_duration_ms(&self) -> i64 {
+        self.keystrokes.last().map_or(0, |last| last.offset_ms)
+    }
+
+    // the typing is played again from the start to the position, because it can't go back
+    fn go_to(&mut self, position: usize) {
+        if position < self.position {
+            self.typing_mode
+                .init(self.text.clone(), Mode::Sentence, self.free);
+            self.position = 0;
+        }
+        while self.position < position {
+            self.play_next();
+        }
+        self.elapsed_ms = self.get_last_keystroke().map_or(0, |k| k.offset_ms) as f64;
+    }
+
+    fn play_next(&mut self) {
+        match self.keystrokes[self.position].pressed {
+            BACKSPACE => self.typing_mode.backspace(),
+            DELETE_WORD => self.typing_mode.delete_word(),
+            pressed => {
+                self.typing_mode.guess(pressed);
+            }
+        }
+        self.position += 1;
+    }
+}
+
+impl App {
+    pub fn get_replays(&self) -> &ReplayList {
+        &self.replays
+    }
+
+    pub fn get_replay(&self) -> Option<&Replay> {
+        self.replay.as_ref()
+    }
+
+    pub fn get_replay_mut(&mut self) -> Option<&mut Replay> {
+        self.replay.as_mut()
+    }
+
+    // only the tests with the keystrokes can be replayed, the newest at first
+    pub fn open_replays(&mut self) {
+        let history = match self.storage.results().read_history() {
+            Ok(history) => history,
+            Err(err) => {
+                self.alert(format!("Cannot read the results: {err}"));
+                return;
+            }
+        };
+
+        self.replays.items = history
+            .into_iter()
+            .rev()
+            // the timed tests of the older versions could be saved without any typed letter
+            .filter(|record| !record.text.is_empty() && !record.keystrokes.is_empty())
+            .map(|record| ReplayItem {
+                timestamp: record.timestamp,
+                text: record.text,
+                wpm: record.results.wpm,
+                total_accuracy: record.results.total_accuracy,
+                free: record.free,
+                keystrokes: record.keystrokes,
+            })
+            .collect();
+        self.replays.selected = 0;
+
+        if self.replays.items.is_empty() {
+            self.alert("There are no tests, that can be replayed, yet.".to_string());
+            return;
+        }
+        self.change_screen(Screens::ReplayList);
+    }
+
+    pub fn select_next_replay(&mut self) {
+        if self.replays.selected + 1 < self.replays.items.len() {
+            self.replays.selected += 1;
+        }
+    }
+
+    pub fn select_previous_replay(&mut self) {
+        self.replays.selected = self.replays.selected.saturating_sub(1);
+    }
+
+    pub fn start_replay(&mut self) {
+        if let Some(item) = self.replays.items.get(self.replays.selected) {
+            self.replay = Some(Replay::new(item));
+            self.change_screen(Screens::Replay);
+        }
+    }
+
+    // the user types the text of the selected test and races against it
+    pub fn race_replay(&mut self) {
+        if let Some(item) = self.replays.items.get(self.replays.selected) {
+            let ghost = Ghost::new(&item.keystrokes);
+            self.start_typing_text(item.text.clone(), Mode::Sentence, Some(ghost));
+        }
+    }
+
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+        self.change_screen(Screens::ReplayList);
+    }
+}
@@ -1,16 +1,25 @@
 use chrono::{DateTime, Local};
 use ratatui::{
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, BufReader, ErrorKind, Write},
+    collections::{HashMap, HashSet},
+    io::{self, ErrorKind},
+    ops::Range,
 };
 
-use crate::misc::MyHasher;
+use crate::{
+    cli::{Challenge, Mode},
+    misc::MyHasher,
+    storage::ResultsStore,
+};
+
+use super::history::{get_accuracy, Keystroke, SessionRecord, BACKSPACE, DELETE_WORD};
+
+// the line breaks of the paragraphs are typed with enter
+pub const LINE_BREAK: &str = "⏎";
 
 struct LetterInfo {
     presses: usize,
@@ -53,6 +62,7 @@ impl LetterInfo {
             main_letter,
             letter_accuracies: letter_presses,
             presses_of_key: self.presses,
+            latencies_ms: vec![],
         }
     }
 }
@@ -64,21 +74,53 @@ pub struct JSONLetterInfo {
     // containes the letters and the accuracy of those letters
     pub letter_accuracies: HashMap<char, usize, MyHasher>,
     presses_of_key: usize,
+    // the time from the previous right keystroke to every right press of this letter
+    #[serde(default)]
+    pub latencies_ms: Vec<i64>,
 }
 
 impl JSONLetterInfo {
+    // the info about the letter from the amount of every key, that was pressed instead of it
+    #[cfg(feature = "sqlite")]
+    pub fn from_presses(
+        main_letter: char,
+        letter_accuracies: HashMap<char, usize, MyHasher>,
+    ) -> JSONLetterInfo {
+        JSONLetterInfo {
+            main_letter,
+            presses_of_key: letter_accuracies.values().sum(),
+            letter_accuracies,
+            latencies_ms: vec![],
+        }
+    }
+
+    // the letter, that was never pressed instead of this one, has 0%
     pub fn get_perc(&self, ch: char) -> f64 {
-        ((*self.letter_accuracies.get(&ch).unwrap() as f64 / self.presses_of_key as f64) * 1000.0)
+        ((*self.letter_accuracies.get(&ch).unwrap_or(&0) as f64 / self.presses_of_key as f64)
+            * 1000.0)
             .round()
             / 10.0
     }
 
+    pub fn get_average_latency(&self) -> Option<f64> {
+        if self.latencies_ms.is_empty() {
+            return None;
+        }
+        let sum: i64 = self.latencies_ms.iter().sum();
+        Some((sum as f64 / self.latencies_ms.len() as f64).round())
+    }
+
+    pub fn get_median_latency(&self) -> Option<f64> {
+        get_median(&self.latencies_ms)
+    }
+
     fn update(&mut self, other: &JSONLetterInfo) {
         // every letter that were tapped
-        self.presses_of_key = self.presses_of_key + other.presses_of_key;
+        self.presses_of_key += other.presses_of_key;
+        self.latencies_ms.extend(&other.latencies_ms);
         for (ch, other) in other.letter_accuracies.iter() {
             if let Some(main) = self.letter_accuracies.get_mut(ch) {
-                *main = *main + *other;
+                *main += *other;
             } else {
                 self.letter_accuracies.insert(*ch, *other);
             }
@@ -89,20 +131,34 @@ impl JSONLetterInfo {
         let copy = self
             .letter_accuracies
             .iter()
-            .map(|(ch, acc)| (*ch, acc.clone()))
+            .map(|(ch, acc)| (*ch, *acc))
             .collect::<HashMap<char, usize, MyHasher>>();
         JSONLetterInfo {
             main_letter: self.main_letter,
             letter_accuracies: copy,
             presses_of_key: self.presses_of_key,
+            latencies_ms: self.latencies_ms.clone(),
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct JSONResults {
+    // the words of 5 characters per minute, the same as in the other typing tools
     pub wpm: f64,
+    // all the pressed keys, also the wrong ones, as the words of 5 characters per minute
+    #[serde(default)]
+    pub raw_wpm: f64,
+    // wpm minus the wrong keys per minute
+    #[serde(default)]
+    pub net_wpm: f64,
+    // the typed characters per minute
+    #[serde(default)]
+    pub cpm: f64,
     pub total_accuracy: f64,
+    // how many times the typed letters were deleted in the free mode
+    #[serde(default)]
+    pub corrections: usize,
     pub letters_info: HashMap<char, JSONLetterInfo, MyHasher>,
 }
 
@@ -128,53 +184,210 @@ impl JSONResults {
 
         let results: HashMap<char, f64, MyHasher> = letter_info
             .letter_accuracies
-            .iter()
-            .map(|(ch, _)| (*ch, letter_info.get_perc(*ch)))
+            .keys()
+            .map(|ch| (*ch, letter_info.get_perc(*ch)))
             .collect();
 
         Ok(results)
     }
 
-    fn new() -> JSONResults {
+    pub fn new() -> JSONResults {
         JSONResults {
             wpm: 0.0,
+            raw_wpm: 0.0,
+            net_wpm: 0.0,
+            cpm: 0.0,
             total_accuracy: 0.0,
+            corrections: 0,
             letters_info: HashMap::with_hasher(MyHasher::new()),
         }
     }
 
-    fn update(&mut self, other: &JSONResults) {
-        if self.wpm != 0.0 {
-            self.wpm = (self.wpm + other.wpm) / 2.0;
-        } else {
-            self.wpm = other.wpm
-        }
-        if self.total_accuracy != 0.0 {
-            self.total_accuracy =
-                ((self.total_accuracy + other.total_accuracy) / 2.0 * 10.0).round() / 10.0;
-        } else {
-            self.total_accuracy = other.total_accuracy;
-        }
-
+    // adds the letters info of the other results, wpm and accuracy are calculated from the whole
+    // history, because they can't be merged without knowing the length of the tests
+    pub fn update(&mut self, other: &JSONResults) {
         for (ch, info_other) in other.letters_info.iter() {
-            if let Some(info_main) = self.letters_info.get_mut(&ch) {
-                info_main.update(&info_other);
+            if let Some(info_main) = self.letters_info.get_mut(ch) {
+                info_main.update(info_other);
             } else {
                 self.letters_info.insert(*ch, info_other.get_copy());
             }
         }
     }
+
+    // calculates the speeds of the test from the typed characters, all the pressed keys and the
+    // time of typing
+    pub fn set_speeds(&mut self, chars: usize, presses: usize, duration_ms: i64) {
+        if duration_ms <= 0 {
+            (self.wpm, self.raw_wpm, self.net_wpm, self.cpm) = (0.0, 0.0, 0.0, 0.0);
+            return;
+        }
+        let minutes = duration_ms as f64 / 60000.0;
+        let errors = presses.saturating_sub(chars) as f64;
+        let wpm = chars as f64 / 5.0 / minutes;
+
+        self.wpm = round_tenth(wpm);
+        self.raw_wpm = round_tenth(presses as f64 / 5.0 / minutes);
+        self.net_wpm = round_tenth((wpm - errors / minutes).max(0.0));
+        self.cpm = round_tenth(chars as f64 / minutes);
+    }
+
+    // the time of every right keystroke since the previous right one belongs to its letter, the
+    // first letter of the test has no latency
+    pub fn set_latencies(&mut self, keystrokes: &[Keystroke]) {
+        let mut previous = None;
+        for keystroke in keystrokes.iter().filter(|keystroke| keystroke.correct) {
+            if let (Some(previous), Some(info)) =
+                (previous, self.letters_info.get_mut(&keystroke.expected))
+            {
+                info.latencies_ms.push(keystroke.offset_ms - previous);
+            }
+            previous = Some(keystroke.offset_ms);
+        }
+    }
+
+    // the average and the median time to press any letter
+    pub fn get_latency(&self) -> Option<(f64, f64)> {
+        let latencies = self
+            .letters_info
+            .values()
+            .flat_map(|info| info.latencies_ms.iter().copied())
+            .collect::<Vec<i64>>();
+        let median = get_median(&latencies)?;
+        let average = latencies.iter().sum::<i64>() as f64 / latencies.len() as f64;
+        Some((average.round(), median))
+    }
+
+    // the average latency of every letter
+    pub fn get_latency_results(&self) -> HashMap<char, f64, MyHasher> {
+        self.letters_info
+            .iter()
+            .filter_map(|(ch, info)| Some((*ch, info.get_average_latency()?)))
+            .collect()
+    }
+
+    // the amount of the right and of all the presses
+    pub fn get_presses(&self) -> (usize, usize) {
+        self.letters_info
+            .values()
+            .fold((0, 0), |(right, all), info| {
+                (
+                    right + info.letter_accuracies.get(&info.main_letter).unwrap_or(&0),
+                    all + info.presses_of_key,
+                )
+            })
+    }
+
+    pub fn get_copy(&self) -> JSONResults {
+        JSONResults {
+            wpm: self.wpm,
+            raw_wpm: self.raw_wpm,
+            net_wpm: self.net_wpm,
+            cpm: self.cpm,
+            total_accuracy: self.total_accuracy,
+            corrections: self.corrections,
+            letters_info: self
+                .letters_info
+                .iter()
+                .map(|(ch, info)| (*ch, info.get_copy()))
+                .collect(),
+        }
+    }
+}
+
+// the earlier run on the same text, it moves through the text with the time of that run
+pub struct Ghost {
+    // the time of every right typed letter
+    letter_times: Vec<i64>,
+}
+
+impl Ghost {
+    pub fn new(keystrokes: &[Keystroke]) -> Ghost {
+        Ghost {
+            letter_times: keystrokes
+                .iter()
+                .filter(|keystroke| keystroke.correct)
+                .map(|keystroke| keystroke.offset_ms)
+                .collect(),
+        }
+    }
+
+    // how many letters the ghost has typed after this time
+    fn get_position(&self, elapsed_ms: i64) -> usize {
+        self.letter_times
+            .iter()
+            .take_while(|time| **time <= elapsed_ms)
+            .count()
+    }
+}
+
+// the results while typing, shown over the text
+pub struct LiveStats {
+    pub wpm: f64,
+    pub accuracy: f64,
+    pub errors: usize,
+    pub elapsed_ms: i64,
+    // the time, that is left in the timed modes
+    pub remaining_ms: Option<i64>,
+    pub typed: usize,
+    // the typed and all the words of the word modes
+    pub words: Option<(usize, usize)>,
+    // the typed part of the text in percents
+    pub progress: u16,
+    // the deleted letters in the free mode
+    pub corrections: Option<usize>,
+}
+
+// how the test goes on after the pressed key
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Guess {
+    Right,
+    Wrong,
+    Finished,
+    // the challenge is failed, the test is not saved
+    Failed,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FailReason {
+    Mistake { expected: char, pressed: char },
+    Accuracy { accuracy: f64, gate: f64 },
+}
+
+// the failed test of the challenge with its results at the moment of the failure
+pub struct Failure {
+    pub reason: FailReason,
+    pub stats: LiveStats,
 }
 
 pub struct TypingMode {
     start_time: Option<DateTime<Local>>,
     current_text: String,
+    // the timed modes end with the time, the text is added to them while typing
+    mode: Mode,
+    // the wrong letters are typed into the text and must be deleted with backspace
+    free: bool,
+    // if every typed letter of the free mode is right, the cursor is after the last one
+    typed: Vec<bool>,
+    // the most letters, that were typed right one after another in the free mode
+    furthest: usize,
+    corrections: usize,
+    challenge: Challenge,
+    // the accuracy gate in percents
+    gate: f64,
+    failure: Option<Failure>,
 
     presses: usize,
+    right_presses: usize,
+    // the places of the text, that were already typed right, the retyped letters of the free mode
+    // are not right presses again
+    right_places: HashSet<usize>,
     correct_letter: char,
     last_guessed: bool,
     guessed_letters: usize,
     results: HashMap<char, LetterInfo>,
+    keystrokes: Vec<Keystroke>,
+    ghost: Option<Ghost>,
 
     result_data: Option<JSONResults>,
 }
@@ -183,44 +396,81 @@ impl TypingMode {
     pub fn new() -> TypingMode {
         TypingMode {
             current_text: "init value".to_string(),
+            mode: Mode::Sentence,
+            free: false,
+            typed: vec![],
+            furthest: 0,
+            corrections: 0,
+            challenge: Challenge::None,
+            gate: 0.0,
+            failure: None,
             correct_letter: 'i',
             last_guessed: true,
             guessed_letters: 0,
             presses: 0,
+            right_presses: 0,
+            right_places: HashSet::new(),
             start_time: None,
             result_data: None,
             results: HashMap::new(),
+            keystrokes: vec![],
+            ghost: None,
         }
     }
 
-    pub fn init(&mut self, text: String) {
+    pub fn init(&mut self, text: String, mode: Mode, free: bool) {
         self.current_text = text;
+        self.mode = mode;
+        self.free = free;
+        self.challenge = Challenge::None;
         self.result_data = None;
+        self.ghost = None;
         self.reload_typing();
     }
 
     pub fn reload_typing(&mut self) {
         self.presses = 0;
+        self.right_presses = 0;
+        self.right_places = HashSet::new();
+        self.typed = vec![];
+        self.furthest = 0;
+        self.corrections = 0;
+        self.failure = None;
         self.guessed_letters = 0;
         self.last_guessed = true;
-        // this will always be a letter, because we have only &str that are not empty
-        self.correct_letter = self.current_text.chars().nth(self.guessed_letters).unwrap();
+        // the empty text has no letter, that could be typed
+        if let Some(ch) = self.current_text.chars().next() {
+            self.correct_letter = ch;
+        }
         self.start_time = None;
         self.results = HashMap::new();
+        self.keystrokes = vec![];
     }
 
-    pub fn guess(&mut self, pressed_key: char) -> Option<bool> {
-        if self.start_time.is_none() {
-            self.start_time = Some(Local::now());
+    pub fn guess(&mut self, pressed_key: char) -> Guess {
+        // the key after the end of the time is not counted, the test is ended
+        if self.is_time_over() {
+            return Guess::Finished;
         }
-
-        self.presses += 1;
-        if let Some(pair) = self.results.get_mut(&self.correct_letter) {
-            pair.insert_press(pressed_key);
-        } else {
-            self.results
-                .insert(self.correct_letter, LetterInfo::new(pressed_key));
+        let expected = self.correct_letter;
+        let guess = match self.free {
+            true => self.guess_free(pressed_key),
+            false => self.guess_strict(pressed_key),
+        };
+        match self.get_fail_reason(expected, pressed_key) {
+            Some(reason) => {
+                self.failure = Some(Failure {
+                    reason,
+                    stats: self.get_live_stats(),
+                });
+                Guess::Failed
+            }
+            None => guess,
         }
+    }
+
+    fn guess_strict(&mut self, pressed_key: char) -> Guess {
+        self.insert_press(pressed_key, pressed_key == self.correct_letter);
 
         // if user typed right letter
         if pressed_key == self.correct_letter {
@@ -230,37 +480,251 @@ impl TypingMode {
                 self.correct_letter = ch;
                 self.last_guessed = true;
                 self.guessed_letters += 1;
-                Some(true)
-            // if there are no letters more return the Err(())
+                Guess::Right
+            // if there are no letters more the test is finished
             } else {
                 self.guessed_letters += 1;
-                self.result_calculation();
-                None
+                Guess::Finished
             }
         // if the user typed wrong letter
         } else {
             self.last_guessed = false;
-            Some(false)
+            Guess::Wrong
         }
     }
 
-    // this function writes the results in the json file
-    fn result_calculation(&mut self) {
-        let file =
-            File::open("results.json").unwrap_or_else(|_| File::create("results.json").unwrap());
-        let read_buf = BufReader::new(file);
-        let mut readed_json: JSONResults =
-            serde_json::from_reader(read_buf).unwrap_or(JSONResults::new());
+    // the wrong letter moves the cursor too, the test ends, when every letter is typed right
+    fn guess_free(&mut self, pressed_key: char) -> Guess {
+        // the wrong letters at the end of the text must be deleted at first
+        if self.typed.len() == self.current_text.chars().count() {
+            return Guess::Wrong;
+        }
+        let right = pressed_key == self.correct_letter;
+        // only the right letter on the new place is the right keystroke, so the retyped letters
+        // have no latency and the ghost of this run moves as the text was typed at first
+        let new = right && self.typed.len() == self.furthest && !self.typed.contains(&false);
+        self.insert_press(pressed_key, new);
+        if new {
+            self.furthest += 1;
+        }
+        self.typed.push(right);
+        self.move_cursor();
+
+        match (
+            self.get_remaining_chars() == 0 && !self.typed.contains(&false),
+            right,
+        ) {
+            (true, _) => Guess::Finished,
+            (false, true) => Guess::Right,
+            (false, false) => Guess::Wrong,
+        }
+    }
 
-        let typing_time = Local::now().signed_duration_since(self.start_time.unwrap());
+    // the challenge of the next tests, the replays have no challenge
+    pub fn set_challenge(&mut self, challenge: Challenge, gate: f64) {
+        self.challenge = challenge;
+        self.gate = gate;
+    }
 
-        let words: Vec<&str> = self.current_text.split_whitespace().collect();
-        let wpm = (words.len() as f64 / typing_time.num_milliseconds() as f64 * 60000.0 * 10.0)
-            .round()
-            / 10.0;
+    // the sudden death fails with the first mistake, the accuracy gate already, when the accuracy
+    // can't get over the gate anymore
+    fn get_fail_reason(&self, expected: char, pressed: char) -> Option<FailReason> {
+        match self.challenge {
+            Challenge::SuddenDeath if pressed != expected => {
+                Some(FailReason::Mistake { expected, pressed })
+            }
+            Challenge::AccuracyGate => self
+                .get_best_accuracy()
+                .filter(|best| *best < self.gate)
+                .map(|_| self.get_gate_failure()),
+            _ => None,
+        }
+    }
+
+    // the accuracy, if every letter left is typed right, the timed and the free tests can always
+    // get better with more right keys
+    fn get_best_accuracy(&self) -> Option<f64> {
+        if self.is_timed() || self.free {
+            return None;
+        }
+        let left = self.get_remaining_chars();
+        Some(get_accuracy(self.right_presses + left, self.presses + left))
+    }
+
+    fn get_gate_failure(&self) -> FailReason {
+        FailReason::Accuracy {
+            accuracy: get_accuracy(self.right_presses, self.presses),
+            gate: self.gate,
+        }
+    }
+
+    // the whole test is typed, but its accuracy can be still below the gate, true if it is failed
+    pub fn check_gate(&mut self) -> bool {
+        if self.challenge != Challenge::AccuracyGate
+            || get_accuracy(self.right_presses, self.presses) >= self.gate
+        {
+            return false;
+        }
+        self.failure = Some(Failure {
+            reason: self.get_gate_failure(),
+            stats: self.get_live_stats(),
+        });
+        true
+    }
+
+    pub fn get_failure(&self) -> Option<&Failure> {
+        self.failure.as_ref()
+    }
+
+    // deletes the last typed letter of the free mode
+    pub fn backspace(&mut self) {
+        if !self.free || self.typed.is_empty() || self.is_time_over() {
+            return;
+        }
+        self.insert_keystroke(BACKSPACE, false);
+        self.corrections += 1;
+        self.typed.pop();
+        self.move_cursor();
+    }
 
-        let total_accuracy =
-            ((self.guessed_letters as f64 / self.presses as f64) * 1000.0).round() / 10.0;
+    // deletes the typed letters back to the start of the word, the spaces before the cursor too
+    pub fn delete_word(&mut self) {
+        if !self.free || self.typed.is_empty() || self.is_time_over() {
+            return;
+        }
+        self.insert_keystroke(DELETE_WORD, false);
+        self.corrections += 1;
+        let typed = self
+            .current_text
+            .chars()
+            .take(self.typed.len())
+            .collect::<Vec<char>>();
+        let spaces = typed
+            .iter()
+            .rev()
+            .take_while(|ch| ch.is_whitespace())
+            .count();
+        let word = typed
+            .iter()
+            .rev()
+            .skip(spaces)
+            .take_while(|ch| !ch.is_whitespace())
+            .count();
+        self.typed.truncate(typed.len() - spaces - word);
+        self.move_cursor();
+    }
+
+    // the letter under the cursor of the free mode is the next expected one
+    fn move_cursor(&mut self) {
+        self.guessed_letters = self.typed.len();
+        self.last_guessed = self.typed.last().copied().unwrap_or(true);
+        if let Some(ch) = self.current_text.chars().nth(self.guessed_letters) {
+            self.correct_letter = ch;
+        }
+    }
+
+    // the pressed key, when the expected letter is under the cursor
+    fn insert_press(&mut self, pressed_key: char, correct: bool) {
+        self.insert_keystroke(pressed_key, correct);
+        self.presses += 1;
+        if pressed_key == self.correct_letter && self.right_places.insert(self.guessed_letters) {
+            self.right_presses += 1;
+        }
+        if let Some(pair) = self.results.get_mut(&self.correct_letter) {
+            pair.insert_press(pressed_key);
+        } else {
+            self.results
+                .insert(self.correct_letter, LetterInfo::new(pressed_key));
+        }
+    }
+
+    fn insert_keystroke(&mut self, pressed_key: char, correct: bool) {
+        let now = Local::now();
+        let start_time = *self.start_time.get_or_insert(now);
+        self.keystrokes.push(Keystroke {
+            expected: self.correct_letter,
+            pressed: pressed_key,
+            offset_ms: now.signed_duration_since(start_time).num_milliseconds(),
+            correct,
+        });
+    }
+
+    pub fn is_timed(&self) -> bool {
+        self.mode.get_time_limit_ms().is_some()
+    }
+
+    pub fn is_time_over(&self) -> bool {
+        self.get_remaining_ms() == Some(0)
+    }
+
+    // the countdown starts with the first key
+    fn get_remaining_ms(&self) -> Option<i64> {
+        let limit = self.mode.get_time_limit_ms()?;
+        Some((limit - self.get_elapsed_ms()).max(0))
+    }
+
+    fn get_elapsed_ms(&self) -> i64 {
+        self.start_time.map_or(0, |start_time| {
+            Local::now()
+                .signed_duration_since(start_time)
+                .num_milliseconds()
+        })
+    }
+
+    // the word is typed with the space after it, the last one with its last letter
+    fn get_typed_words(&self) -> Option<(usize, usize)> {
+        let count = self.mode.get_word_count()?;
+        if self.get_remaining_chars() == 0 {
+            return Some((count, count));
+        }
+        let typed = self
+            .current_text
+            .chars()
+            .take(self.guessed_letters)
+            .filter(|ch| ch.is_whitespace())
+            .count();
+        Some((typed, count))
+    }
+
+    // the race against a test with the line breaks needs them, even if they are turned off
+    pub fn is_line_break_expected(&self) -> bool {
+        self.get_remaining_chars() > 0 && self.correct_letter == '\n'
+    }
+
+    pub fn get_typed_chars(&self) -> usize {
+        self.guessed_letters
+    }
+
+    // the letters, that are not typed yet
+    pub fn get_remaining_chars(&self) -> usize {
+        self.current_text.chars().count() - self.guessed_letters
+    }
+
+    // the timed modes get the next text, before the user comes to the end
+    pub fn append_text(&mut self, text: &str) {
+        self.current_text.push(' ');
+        self.current_text.push_str(text);
+    }
+
+    // this function appends the results of this test to the history file
+    pub fn result_calculation(&mut self, store: &dyn ResultsStore) -> Result<(), io::Error> {
+        // the timed tests are measured over their time, the key after it wasn't counted
+        let typing_time = match self.mode.get_time_limit_ms() {
+            Some(limit) => limit.min(self.get_elapsed_ms()),
+            None => self.get_elapsed_ms(),
+        };
+        // only the typed part of the streamed text belongs to the test
+        let text = match self.is_timed() {
+            true => self
+                .current_text
+                .chars()
+                .take(self.guessed_letters)
+                .collect(),
+            false => self.current_text.clone(),
+        };
+
+        // the same as in the global results, so one test has the same accuracy there
+        let total_accuracy = get_accuracy(text.chars().count(), self.presses);
 
         let letters_info: HashMap<char, JSONLetterInfo, MyHasher> = self
             .results
@@ -268,52 +732,282 @@ impl TypingMode {
             .map(|(ch, letter_info)| (*ch, letter_info.to_json(*ch)))
             .collect();
 
-        let new_json: JSONResults = JSONResults {
-            wpm,
+        let mut new_json: JSONResults = JSONResults {
             total_accuracy,
+            corrections: self.corrections,
             letters_info,
+            ..JSONResults::new()
         };
-
-        readed_json.update(&new_json);
+        new_json.set_speeds(text.chars().count(), self.presses, typing_time);
+        new_json.set_latencies(&self.keystrokes);
+
+        let mut record = SessionRecord::new(
+            text,
+            self.mode,
+            new_json.get_copy(),
+            typing_time,
+            std::mem::take(&mut self.keystrokes),
+        );
+        record.free = self.free;
         self.result_data = Some(new_json);
 
-        let mut file = File::create("results.json").unwrap();
-        file.write_all(serde_json::to_string(&readed_json).unwrap().as_bytes())
-            .unwrap();
+        store.append_session(&record)
+    }
+
+    // the ghost starts with the first pressed key, as the run of the ghost did
+    pub fn set_ghost(&mut self, ghost: Ghost) {
+        self.ghost = Some(ghost);
+    }
+
+    pub fn has_ghost(&self) -> bool {
+        self.ghost.is_some()
+    }
+
+    fn get_ghost_position(&self) -> Option<usize> {
+        let ghost = self.ghost.as_ref()?;
+        match self.start_time {
+            Some(start_time) => Some(
+                ghost.get_position(
+                    Local::now()
+                        .signed_duration_since(start_time)
+                        .num_milliseconds(),
+                ),
+            ),
+            None => Some(0),
+        }
+    }
+
+    // how many letters the user is ahead of the ghost, the negative amount is behind
+    pub fn get_ghost_diff(&self) -> Option<i64> {
+        self.get_ghost_position()
+            .map(|position| self.guessed_letters as i64 - position as i64)
     }
 
     pub fn get_last_results(&self) -> &JSONResults {
         self.result_data.as_ref().unwrap()
     }
 
-    pub fn get_text_to_render(&self) -> Line {
-        let mut parts: Vec<Span> = vec![];
+    pub fn is_started(&self) -> bool {
+        self.start_time.is_some()
+    }
 
-        // the guessed part, that should be green
-        parts.push(Span::styled(
-            // from start guessed letter forward
-            &self.current_text[..self.guessed_letters],
-            Style::default().fg(Color::Green),
-        ));
+    // the results of the typed part of the text, they change with every key and with the time
+    pub fn get_live_stats(&self) -> LiveStats {
+        let elapsed_ms = self.get_elapsed_ms();
+        let wpm = match elapsed_ms {
+            0 => 0.0,
+            elapsed_ms => {
+                round_tenth(self.guessed_letters as f64 / 5.0 / (elapsed_ms as f64 / 60000.0))
+            }
+        };
+        let accuracy = match self.presses {
+            0 => 100.0,
+            presses => get_accuracy(self.right_presses, presses),
+        };
+
+        LiveStats {
+            wpm,
+            accuracy,
+            errors: self.presses - self.right_presses,
+            elapsed_ms,
+            remaining_ms: self.get_remaining_ms(),
+            typed: self.guessed_letters,
+            words: self.get_typed_words(),
+            progress: (self.guessed_letters * 100 / self.current_text.chars().count().max(1))
+                as u16,
+            corrections: self.free.then_some(self.corrections),
+        }
+    }
 
-        // if the last guess was wrong it will be equal to 1 otherwise 0
-        let guess_bit = if self.last_guessed { 0 } else { 1 };
+    // the text wrapped into the rows of this width and the row with the cursor
+    pub fn get_text_to_render(&self, width: usize) -> (Vec<Line<'_>>, usize) {
+        // the letter, where the ghost is now
+        let ghost = self.get_ghost_position();
+        let letters = self
+            .current_text
+            .char_indices()
+            .collect::<Vec<(usize, char)>>();
+        let rows = wrap_text(&self.current_text, width);
+
+        let lines = rows
+            .iter()
+            .map(|row| {
+                // the letters with the same style are in one span
+                let mut spans: Vec<Span> = vec![];
+                let mut part: Option<(usize, Style)> = None;
+                for i in row.clone() {
+                    let (byte, ch) = letters[i];
+                    let mut style = self.get_letter_style(i, ch);
+                    if ghost == Some(i) {
+                        style = style.bg(Color::DarkGray).add_modifier(Modifier::UNDERLINED);
+                    }
+                    match part {
+                        Some((_, part_style)) if part_style == style && ch != '\n' => {}
+                        Some((start, part_style)) => {
+                            spans.push(Span::styled(&self.current_text[start..byte], part_style));
+                            part = Some((byte, style));
+                        }
+                        None => part = Some((byte, style)),
+                    }
+                    // the line break is the last letter of its row, it must be seen to be typed
+                    if ch == '\n' {
+                        spans.push(Span::styled(LINE_BREAK, style));
+                        part = None;
+                    }
+                }
+                if let Some((start, style)) = part {
+                    let end = letters
+                        .get(row.end)
+                        .map_or(self.current_text.len(), |(byte, _)| *byte);
+                    spans.push(Span::styled(&self.current_text[start..end], style));
+                }
+                Line::from(spans)
+            })
+            .collect();
+
+        // the cursor after the last letter is in the last row
+        let cursor_row = rows
+            .iter()
+            .position(|row| row.contains(&self.guessed_letters))
+            .unwrap_or(rows.len().saturating_sub(1));
+        (lines, cursor_row)
+    }
 
-        // the part that should be red
-        parts.push(Span::styled(
-            // RevertAddBool adds + 1 if its false
+    fn get_letter_style(&self, i: usize, ch: char) -> Style {
+        let wrong = Style::default().fg(Color::Red);
+        if self.free {
+            return match self.typed.get(i) {
+                Some(true) => Style::default().fg(Color::Green),
+                // the wrong space can't be seen in red
+                Some(false) if ch == ' ' => wrong.bg(Color::Red),
+                Some(false) => wrong,
+                None if i == self.guessed_letters => Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::UNDERLINED),
+                None => Style::default().fg(Color::White),
+            };
+        }
+        match i {
+            // the guessed part
+            i if i < self.guessed_letters => Style::default().fg(Color::Green),
             // only one letter, if the last guess was wrong
-            &self.current_text[self.guessed_letters..self.guessed_letters + guess_bit],
-            Style::default().fg(Color::Red),
-        ));
-
-        // the default text
-        parts.push(Span::styled(
-            // all from guessed letters and + 1 if the last was wrong
-            &self.current_text[self.guessed_letters + guess_bit..],
-            Style::default().fg(Color::White),
-        ));
-
-        Line::from(parts)
+            i if i == self.guessed_letters && !self.last_guessed => wrong,
+            _ => Style::default().fg(Color::White),
+        }
+    }
+}
+
+// the ranges of the letters of every row, the rows are wrapped after the last space, that fits
+// into the width, and after every line break
+pub fn wrap_text(text: &str, width: usize) -> Vec<Range<usize>> {
+    let width = width.max(1);
+    let mut rows = vec![];
+    let (mut start, mut last_space) = (0, None);
+    for (i, ch) in text.chars().enumerate() {
+        if i - start >= width {
+            match last_space {
+                Some(space) => {
+                    rows.push(start..space + 1);
+                    start = space + 1;
+                }
+                // the word longer than the row is cut
+                None => {
+                    rows.push(start..i);
+                    start = i;
+                }
+            }
+            last_space = None;
+        }
+        match ch {
+            '\n' => {
+                rows.push(start..i + 1);
+                (start, last_space) = (i + 1, None);
+            }
+            ' ' => last_space = Some(i),
+            _ => (),
+        }
+    }
+    let len = text.chars().count();
+    if start < len || rows.is_empty() {
+        rows.push(start..len);
+    }
+    rows
+}
+
+fn round_tenth(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+fn get_median(values: &[i64]) -> Option<f64> {
+    let mut values = values.to_vec();
+    values.sort();
+    let middle = values.len() / 2;
+    match values.len() {
+        0 => None,
+        len if len % 2 == 0 => Some((values[middle - 1] + values[middle]) as f64 / 2.0),
+        _ => Some(values[middle] as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, path::Path};
+
+    use crate::{app::history::get_global_results, config::Weighting};
+
+    use super::*;
+
+    // keeps the appended tests as json in the memory
+    struct MemoryStore(RefCell<Vec<String>>);
+
+    impl ResultsStore for MemoryStore {
+        fn path(&self) -> &Path {
+            Path::new("memory")
+        }
+
+        fn read_history(&self) -> Result<Vec<SessionRecord>, io::Error> {
+            let history = self.0.borrow();
+            let records = history.iter().map(|line| serde_json::from_str(line));
+            Ok(records.collect::<Result<_, _>>()?)
+        }
+
+        fn append_session(&self, record: &SessionRecord) -> Result<(), io::Error> {
+            self.0.borrow_mut().push(serde_json::to_string(record)?);
+            Ok(())
+        }
+
+        fn clear(&self) -> Result<(), io::Error> {
+            self.0.borrow_mut().clear();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn retyped_letters_are_right_only_once() {
+        let mut typing = TypingMode::new();
+        typing.init("abc".to_string(), Mode::Sentence, true);
+        for ch in "abx".chars() {
+            typing.guess(ch);
+        }
+        typing.delete_word();
+        for ch in "abc".chars() {
+            typing.guess(ch);
+        }
+
+        let store = MemoryStore(RefCell::new(vec![]));
+        typing.result_calculation(&store).unwrap();
+        let global = get_global_results(&store.read_history().unwrap(), Weighting::Time).unwrap();
+
+        assert_eq!(typing.get_live_stats().accuracy, 50.0);
+        assert_eq!(typing.get_last_results().total_accuracy, 50.0);
+        assert_eq!(global.total_accuracy, 50.0);
+    }
+
+    #[test]
+    fn text_is_wrapped_after_the_spaces_and_the_line_breaks() {
+        assert_eq!(
+            wrap_text("ab cd ef\ngh abcdefgh", 6),
+            [0..6, 6..9, 9..12, 12..18, 18..20]
+        );
     }
 }
@@ -0,0 +1,215 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Backend, KeyboardLayout},
+    storage::profiles::DEFAULT_PROFILE,
+};
+
+/// A simple CLI tool for blind typing test
+#[derive(Parser, Debug, Clone)]
+// help is included automaticly and the version will print the version of the app out of the
+// cargo.toml file
+#[command(version, about)]
+pub struct Args {
+    /// Profile with its own results, texts and settings
+    #[arg(long, global = true, default_value = DEFAULT_PROFILE)]
+    pub profile: String,
+
+    /// File with the texts for typing, one text per line [default: $XDG_DATA_HOME/blind_typing/profiles/<profile>/texts.txt]
+    #[arg(long, global = true)]
+    pub texts: Option<PathBuf>,
+
+    /// File where the history of all the typing tests is stored [default: $XDG_DATA_HOME/blind_typing/profiles/<profile>/history.jsonl]
+    #[arg(long, global = true)]
+    pub results: Option<PathBuf>,
+
+    /// Where the results are stored [default: json]
+    #[arg(long, global = true, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// Kind of the typing test [default: sentence]
+    #[arg(long, global = true, value_enum)]
+    pub mode: Option<Mode>,
+
+    /// The texts are the paragraphs separated with the empty lines
+    #[arg(long, global = true)]
+    pub paragraphs: bool,
+
+    /// Type the line breaks of the paragraphs with enter
+    #[arg(long, global = true)]
+    pub line_breaks: bool,
+
+    /// Challenge, that fails the test with too many mistakes [default: none]
+    #[arg(long, global = true, value_enum)]
+    pub challenge: Option<Challenge>,
+
+    /// Accuracy in percents, below which the test of the accuracy gate fails [default: 95]
+    #[arg(long, global = true, value_parser = parse_gate)]
+    pub gate: Option<f64>,
+
+    /// Keyboard layout, that is shown on the screen [default: qwerty]
+    #[arg(long, global = true, value_enum)]
+    pub layout: Option<KeyboardLayout>,
+
+    /// Seed for choosing the texts, the same seed gives the same order of texts
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Race against the fastest earlier run on the same text
+    #[arg(long, global = true)]
+    pub ghost: bool,
+
+    /// Type the wrong letters into the text and fix them with backspace
+    #[arg(long, global = true)]
+    pub free: bool,
+
+    /// Type this text instead of the texts from the file
+    #[arg(long, global = true, value_parser = parse_text)]
+    pub text: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    /// Start the typing app (default)
+    Run,
+    /// Print the global results of all the typing tests
+    Stats,
+    /// Delete all the stored results
+    Reset,
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Type one random sentence from the texts to the end
+    Sentence,
+    /// Type the texts for 15 seconds
+    Time15,
+    /// Type the texts for 30 seconds
+    Time30,
+    /// Type the texts for 60 seconds
+    Time60,
+    /// Type the texts for 120 seconds
+    Time120,
+    /// Type 10 random words from the texts
+    Words10,
+    /// Type 25 random words from the texts
+    Words25,
+    /// Type 50 random words from the texts
+    Words50,
+    /// Type 100 random words from the texts
+    Words100,
+}
+
+impl Mode {
+    // the order, in which the modes are switched on the main screen
+    const ALL: [Mode; 9] = [
+        Mode::Sentence,
+        Mode::Time15,
+        Mode::Time30,
+        Mode::Time60,
+        Mode::Time120,
+        Mode::Words10,
+        Mode::Words25,
+        Mode::Words50,
+        Mode::Words100,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Mode::Sentence => "sentence",
+            Mode::Time15 => "15 seconds",
+            Mode::Time30 => "30 seconds",
+            Mode::Time60 => "60 seconds",
+            Mode::Time120 => "120 seconds",
+            Mode::Words10 => "10 words",
+            Mode::Words25 => "25 words",
+            Mode::Words50 => "50 words",
+            Mode::Words100 => "100 words",
+        }
+    }
+
+    // the time of the timed modes, the text streams in until it is over
+    pub fn get_time_limit_ms(&self) -> Option<i64> {
+        match self {
+            Mode::Time15 => Some(15_000),
+            Mode::Time30 => Some(30_000),
+            Mode::Time60 => Some(60_000),
+            Mode::Time120 => Some(120_000),
+            _ => None,
+        }
+    }
+
+    // the length of the test in the word modes, every test has the same amount of words
+    pub fn get_word_count(&self) -> Option<usize> {
+        match self {
+            Mode::Words10 => Some(10),
+            Mode::Words25 => Some(25),
+            Mode::Words50 => Some(50),
+            Mode::Words100 => Some(100),
+            _ => None,
+        }
+    }
+
+    pub fn get_next(&self) -> Mode {
+        let i = Mode::ALL.iter().position(|mode| mode == self).unwrap();
+        Mode::ALL[(i + 1) % Mode::ALL.len()]
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Challenge {
+    /// Every finished test counts
+    None,
+    /// The first mistake fails the test
+    SuddenDeath,
+    /// The test fails, if its accuracy is below the gate
+    AccuracyGate,
+}
+
+impl Challenge {
+    const ALL: [Challenge; 3] = [
+        Challenge::None,
+        Challenge::SuddenDeath,
+        Challenge::AccuracyGate,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Challenge::None => "no challenge",
+            Challenge::SuddenDeath => "sudden death",
+            Challenge::AccuracyGate => "accuracy gate",
+        }
+    }
+
+    pub fn get_next(&self) -> Challenge {
+        let i = Challenge::ALL
+            .iter()
+            .position(|challenge| challenge == self)
+            .unwrap();
+        Challenge::ALL[(i + 1) % Challenge::ALL.len()]
+    }
+}
+
+// there is nothing to type in the empty text
+fn parse_text(value: &str) -> Result<String, String> {
+    match value.trim().is_empty() {
+        true => Err("the text must not be empty".to_string()),
+        false => Ok(value.to_string()),
+    }
+}
+
+fn parse_gate(value: &str) -> Result<f64, String> {
+    let gate = value.parse::<f64>().map_err(|err| err.to_string())?;
+    match (0.0..=100.0).contains(&gate) {
+        true => Ok(gate),
+        false => Err("the gate must be between 0 and 100".to_string()),
+    }
+}
@@ -0,0 +1,356 @@
+use std::{collections::HashSet, fmt, io, path::PathBuf};
+
+use ratatui::style::Color;
+use serde::Deserialize;
+
+use crate::{
+    cli::{Args, Challenge, Mode},
+    storage,
+};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "Cannot read the config file: {err}"),
+            ConfigError::Parse(err) => write!(f, "The config file is not valid:\n{err}"),
+            ConfigError::Invalid(msg) => write!(f, "The config file is not valid: {msg}"),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err)
+    }
+}
+
+// the settings of the app from the config.toml, every field can be omitted
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub mode: Mode,
+    // the file with the texts, the default one is in the data directory
+    pub texts: Option<PathBuf>,
+    pub layout: KeyboardLayout,
+    pub colors: ColorThresholds,
+    pub latency: LatencyThresholds,
+    pub weighting: Weighting,
+    pub backend: Backend,
+    pub prompt: String,
+    // the ghost of the fastest earlier run on the same text is shown while typing
+    pub ghost: bool,
+    // the wrong letters are typed into the text and must be deleted with backspace
+    pub free: bool,
+    pub challenge: Challenge,
+    // the texts are the paragraphs separated with the empty lines instead of the single lines
+    pub paragraphs: bool,
+    // the line breaks of the paragraphs must be typed with enter
+    pub line_breaks: bool,
+    // the accuracy in percents, that the tests of the accuracy gate must have
+    pub gate: f64,
+    pub keys: KeyBindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            mode: Mode::Sentence,
+            texts: None,
+            layout: KeyboardLayout::Qwerty,
+            colors: ColorThresholds::default(),
+            latency: LatencyThresholds::default(),
+            weighting: Weighting::Time,
+            backend: Backend::Json,
+            prompt: "Write me 10 sentences, separated with newline and are good for blind typing test, but not the default examples. Write nothing else but the sentences without the numbers".to_string(),
+            ghost: false,
+            free: false,
+            challenge: Challenge::None,
+            paragraphs: false,
+            line_breaks: false,
+            gate: 95.0,
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl Config {
+    // loads the config file of the profile, if there is no config file the default settings are
+    // used
+    pub fn load(profile: &str) -> Result<Config, ConfigError> {
+        let config = match storage::read_config(profile)? {
+            Some(text) => toml::from_str::<Config>(&text)?,
+            None => Config::default(),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    // the flags from the command line are more important than the config file
+    pub fn apply_args(&mut self, args: &Args) {
+        if let Some(mode) = args.mode {
+            self.mode = mode;
+        }
+        if let Some(layout) = args.layout {
+            self.layout = layout;
+        }
+        if let Some(backend) = args.backend {
+            self.backend = backend;
+        }
+        if args.ghost {
+            self.ghost = true;
+        }
+        if args.free {
+            self.free = true;
+        }
+        if args.paragraphs {
+            self.paragraphs = true;
+        }
+        if args.line_breaks {
+            self.line_breaks = true;
+        }
+        if let Some(challenge) = args.challenge {
+            self.challenge = challenge;
+        }
+        if let Some(gate) = args.gate {
+            self.gate = gate;
+        }
+        if args.texts.is_some() {
+            self.texts = args.texts.clone();
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        self.colors.validate()?;
+        self.latency.validate()?;
+        self.keys.validate()?;
+        if !(0.0..=100.0).contains(&self.gate) {
+            return Err(ConfigError::Invalid(format!(
+                "gate must be between 0 and 100, got {}",
+                self.gate
+            )));
+        }
+        if self.prompt.trim().is_empty() {
+            return Err(ConfigError::Invalid("prompt cannot be empty".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyboardLayout {
+    Qwerty,
+    Qwertz,
+    Dvorak,
+    Colemak,
+}
+
+impl KeyboardLayout {
+    // the keys of the keyboard rows one after another: 13 keys, 11 keys, 10 keys and the space
+    pub fn get_keys(&self, uppercase: bool) -> &str {
+        match (self, uppercase) {
+            (KeyboardLayout::Qwerty, false) => "qwertyuiop[]\\asdfghjkl;\'zxcvbnm,./ ",
+            (KeyboardLayout::Qwerty, true) => "QWERTYUIOP{}|ASDFGHJKL:\"ZXCVBNM<>? ",
+            (KeyboardLayout::Qwertz, false) => "qwertzuiopü+#asdfghjklöäyxcvbnm,.- ",
+            (KeyboardLayout::Qwertz, true) => "QWERTZUIOPÜ*'ASDFGHJKLÖÄYXCVBNM;:_ ",
+            (KeyboardLayout::Dvorak, false) => "',.pyfgcrl/=\\aoeuidhtns-;qjkxbmwvz ",
+            (KeyboardLayout::Dvorak, true) => "\"<>PYFGCRL?+|AOEUIDHTNS_:QJKXBMWVZ ",
+            (KeyboardLayout::Colemak, false) => "qwfpgjluy;[]\\arstdhneio'zxcvbkm,./ ",
+            (KeyboardLayout::Colemak, true) => "QWFPGJLUY:{}|ARSTDHNEIO\"ZXCVBKM<>? ",
+        }
+    }
+
+    // the key can be typed only with shift
+    pub fn is_uppercase(&self, ch: char) -> bool {
+        !self.get_keys(false).contains(ch) && self.get_keys(true).contains(ch)
+    }
+}
+
+// where the results are stored, sqlite is only available with the "sqlite" feature
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Json,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+// how the wpm of the tests are weighted in the global results
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weighting {
+    // by the time of typing, so the longer tests are more important
+    Time,
+    // by the amount of the characters in the text
+    Length,
+}
+
+// the accuracy in percents, from which the letters are colored
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorThresholds {
+    pub good: f64,
+    pub medium: f64,
+}
+
+impl Default for ColorThresholds {
+    fn default() -> Self {
+        ColorThresholds {
+            good: 80.0,
+            medium: 50.0,
+        }
+    }
+}
+
+impl ColorThresholds {
+    pub fn get_color(&self, accuracy: f64) -> Color {
+        match accuracy {
+            101.0 => Color::Yellow,
+            0.0 => Color::Reset,
+            perc if perc >= self.good => Color::Green,
+            perc if perc >= self.medium => Color::Blue,
+            perc if perc < self.medium => Color::Red,
+            _ => Color::Reset,
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        for (name, value) in [("colors.good", self.good), ("colors.medium", self.medium)] {
+            if !(0.0..=100.0).contains(&value) {
+                return Err(ConfigError::Invalid(format!(
+                    "{name} must be between 0 and 100, got {value}"
+                )));
+            }
+        }
+        if self.medium > self.good {
+            return Err(ConfigError::Invalid(
+                "colors.medium cannot be bigger than colors.good".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// the average time in milliseconds to press the key, to which the keys are colored in the
+// latency mode of the keyboard
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct LatencyThresholds {
+    pub fast: f64,
+    pub slow: f64,
+}
+
+impl Default for LatencyThresholds {
+    fn default() -> Self {
+        LatencyThresholds {
+            fast: 250.0,
+            slow: 500.0,
+        }
+    }
+}
+
+impl LatencyThresholds {
+    pub fn get_color(&self, latency: f64) -> Color {
+        match latency {
+            0.0 => Color::Reset,
+            ms if ms <= self.fast => Color::Green,
+            ms if ms <= self.slow => Color::Blue,
+            _ => Color::Red,
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.fast <= 0.0 || self.slow <= 0.0 {
+            return Err(ConfigError::Invalid(
+                "latency.fast and latency.slow must be bigger than 0".to_string(),
+            ));
+        }
+        if self.fast > self.slow {
+            return Err(ConfigError::Invalid(
+                "latency.fast cannot be bigger than latency.slow".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// the keys of the main screen
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub quit: char,
+    pub start: char,
+    pub results: char,
+    pub delete_results: char,
+    pub undo_reset: char,
+    pub new_texts: char,
+    pub profiles: char,
+    pub replay: char,
+    pub ngrams: char,
+    pub progress: char,
+    pub confusions: char,
+    pub mode: char,
+    pub free: char,
+    pub challenge: char,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            quit: 'q',
+            start: 's',
+            results: 'r',
+            delete_results: 'R',
+            undo_reset: 'u',
+            new_texts: 't',
+            profiles: 'p',
+            replay: 'v',
+            ngrams: 'n',
+            progress: 'g',
+            confusions: 'c',
+            mode: 'm',
+            free: 'f',
+            challenge: 'x',
+        }
+    }
+}
+
+impl KeyBindings {
+    fn validate(&self) -> Result<(), ConfigError> {
+        let keys = [
+            self.quit,
+            self.start,
+            self.results,
+            self.delete_results,
+            self.undo_reset,
+            self.new_texts,
+            self.profiles,
+            self.replay,
+            self.ngrams,
+            self.progress,
+            self.confusions,
+            self.mode,
+            self.free,
+            self.challenge,
+        ];
+        if keys.iter().collect::<HashSet<&char>>().len() != keys.len() {
+            return Err(ConfigError::Invalid(
+                "every key in [keys] must be different".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
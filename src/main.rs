@@ -1,46 +1,94 @@
 mod app;
+mod cli;
+mod config;
 mod misc;
+mod storage;
 mod ui;
 mod widgets;
 
-use app::{App, Screens};
+use app::{profiles::NameAction, progress::ProgressRange, App, AppEvent, Screens};
+use clap::Parser;
+use cli::{Args, Command, Mode};
+use config::{Config, Weighting};
 use crossterm::{
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
-        KeyModifiers,
+        DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyCode, KeyEvent,
+        KeyEventKind, KeyModifiers,
     },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use futures_util::StreamExt;
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     Terminal,
 };
-use std::io;
+use std::{
+    io::{self, ErrorKind},
+    process,
+    time::Duration,
+};
+use storage::Storage;
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver},
+    time::{self, MissedTickBehavior},
+};
 use ui::ui;
 
-// this is clap
-// This is what will be printed with help
-/// Simple program to greet a person
-// #[derive(Parser, Debug)]
-// help is included automaticly and the version will print the version of the app out of the
-// cargo.toml file
-// #[command(version)]
-// struct Args {
-// this is what will be printed in help to this arg(name)
-// Name of the person to greet
-// this tells that we have both the short and long type of attribute in the cli app
-// #[arg(short, long)]
-// key: String,
-
-// Number of times to greet
-// without the default value the user must write it be yourself
-// #[arg(short, long, default_value_t = 1)]
-// count: u8,
-// }
-
 #[tokio::main]
-async fn main() -> Result<(), io::Error> {
+async fn main() {
+    // the errors are printed for the user and not in the debug form
+    if let Err(err) = run(Args::parse()).await {
+        eprintln!("Error: {err}");
+        if err.kind() == ErrorKind::InvalidData {
+            eprintln!(
+                "Start the app to restore the last backup of the results, that is not broken."
+            );
+        }
+        process::exit(1);
+    }
+}
+
+async fn run(args: Args) -> Result<(), io::Error> {
+    let mut config = Config::load(&args.profile).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(1);
+    });
+    config.apply_args(&args);
+    let storage = Storage::new(
+        &args.profile,
+        config.texts.clone(),
+        args.results.clone(),
+        config.backend,
+    )?;
+    let (weighting, mode) = (config.weighting, config.mode);
+    // the tui migrates the old results itself, after it has checked the history
+    let command = args.command.unwrap_or(Command::Run);
+    if command != Command::Run {
+        if let Err(err) = storage.migrate_legacy_results() {
+            eprintln!("The old results are not migrated: {err}");
+        }
+    }
+
+    match command {
+        Command::Run => run_tui(&args, config, storage).await,
+        Command::Stats => print_stats(&storage, weighting, mode),
+        Command::Reset => {
+            let backup = storage.reset_results()?;
+            println!(
+                "The results in {} were deleted, the backup is saved to {}.",
+                storage.results().path().display(),
+                backup.display()
+            );
+            Ok(())
+        }
+    }
+}
+
+async fn run_tui(args: &Args, config: Config, storage: Storage) -> Result<(), io::Error> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let mut app = App::new(args, config, storage, sender)?;
+
     enable_raw_mode()?;
     let mut stderr = io::stderr();
     execute!(stderr, EnterAlternateScreen, EnableMouseCapture)?;
@@ -48,10 +96,7 @@ async fn main() -> Result<(), io::Error> {
     let backend = CrosstermBackend::new(stderr);
     let mut terminal = ratatui::Terminal::new(backend)?;
 
-    let filename = "texts.txt";
-    let mut app = App::new(filename);
-    // run_app(&mut terminal, &mut app)?;
-    run_app(&mut terminal, &mut app).await?;
+    run_app(&mut terminal, &mut app, receiver).await?;
 
     disable_raw_mode()?;
     execute!(
@@ -63,65 +108,234 @@ async fn main() -> Result<(), io::Error> {
     Ok(())
 }
 
-// running the main loop of the app
-async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<(), io::Error> {
-    loop {
-        terminal.draw(|f| ui(f, app))?;
+// prints the global results of the mode without starting the tui
+fn print_stats(storage: &Storage, weighting: Weighting, mode: Mode) -> Result<(), io::Error> {
+    let results = match storage.results().read_results(weighting, mode) {
+        Ok(results) => results,
+        // the broken results are not the same as no results
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            println!(
+                "There are no results of the {} mode in {} yet.",
+                mode.as_str(),
+                storage.results().path().display()
+            );
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind != KeyEventKind::Press {
-                continue;
-            }
-            if key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL {
-                break;
-            }
-            if let KeyCode::Char(ch) = key.code {
-                app.set_key_pressed(ch);
-            }
+    println!("Mode: {}", mode.as_str());
+    println!("Speed: {} wpm", results.wpm);
+    println!("Net speed: {} wpm", results.net_wpm);
+    println!("Raw speed: {} wpm", results.raw_wpm);
+    println!("Characters per minute: {}", results.cpm);
+    println!("Total accuracy: {}%", results.total_accuracy);
 
-            if key.code == KeyCode::Tab {
-                app.change_uppercase();
-            }
+    let mut letters = results
+        .get_total_results()
+        .into_iter()
+        .collect::<Vec<(char, f64)>>();
+    // the worst letters at first
+    letters.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
 
-            match app.get_current_screen() {
-                Screens::Main => main_behavior(&key, app).await,
-                Screens::Typing => typing_behavior(&key, app),
-                Screens::Exiting => {
-                    if exiting_behavior(&key, app) {
+    for (ch, accuracy) in letters {
+        println!("{ch:?}: {accuracy}%");
+    }
+    Ok(())
+}
+
+// how often the screen is redrawn, when it changes without the pressed keys
+const FRAME: Duration = Duration::from_millis(30);
+
+// running the main loop of the app, it waits for the keys, the frames and the events of the
+// background tasks at the same time
+async fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    mut receiver: UnboundedReceiver<AppEvent>,
+) -> Result<(), io::Error> {
+    let mut keys = EventStream::new();
+    let mut frames = time::interval(FRAME);
+    frames.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        terminal.draw(|f| ui(f, app))?;
+
+        tokio::select! {
+            // the replay, the ghost and the time of typing move on by themselves
+            _ = frames.tick(), if app.needs_frames() => app.tick(),
+            Some(event) = receiver.recv() => app.handle_event(event),
+            key = keys.next() => match key {
+                Some(Ok(Event::Key(key))) => {
+                    if key_behaviour(&key, app) {
                         break;
                     }
                 }
-                Screens::TypingResult => end_typing_behaviour(&key, app),
-                Screens::GlobalResultMain => global_res_behavior(&key, app),
-                Screens::LetterResult => letter_res_behavior(&key, app),
-                Screens::Alert => alert_behaviour(&key, app),
-            }
+                Some(Ok(_)) => (),
+                Some(Err(err)) => return Err(err),
+                None => break,
+            },
         }
     }
     Ok(())
 }
 
+// returns true, if the app should be closed
+fn key_behaviour(key: &KeyEvent, app: &mut App) -> bool {
+    if key.kind != KeyEventKind::Press {
+        return false;
+    }
+    if key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL {
+        return true;
+    }
+    if let KeyCode::Char(ch) = key.code {
+        app.set_key_pressed(ch);
+    }
+
+    if key.code == KeyCode::Tab {
+        app.change_uppercase();
+    }
+    if key.code == KeyCode::BackTab {
+        app.change_latency_mode();
+    }
+
+    match app.get_current_screen() {
+        Screens::Main => main_behavior(key, app),
+        Screens::Typing => typing_behavior(key, app),
+        Screens::Exiting => return exiting_behavior(key, app),
+        Screens::TypingResult => end_typing_behaviour(key, app),
+        Screens::TypingFailed => failed_typing_behaviour(key, app),
+        Screens::GlobalResultMain => global_res_behavior(key, app),
+        Screens::LetterResult => letter_res_behavior(key, app),
+        Screens::Alert | Screens::Info => alert_behaviour(key, app),
+        Screens::Confirm => confirm_behaviour(key, app),
+        Screens::Profiles => profiles_behaviour(key, app),
+        Screens::ProfileName => profile_name_behaviour(key, app),
+        Screens::ReplayList => replay_list_behaviour(key, app),
+        Screens::Replay => replay_behaviour(key, app),
+        Screens::Ngrams => ngrams_behaviour(key, app),
+        Screens::Progress => progress_behaviour(key, app),
+        Screens::Confusions => confusions_behaviour(key, app),
+    }
+    false
+}
+
 fn alert_behaviour(key: &KeyEvent, app: &mut App) {
+    if let KeyCode::Char(_) = key.code {
+        app.change_screen(app.get_previous_screen())
+    }
+}
+
+fn confirm_behaviour(key: &KeyEvent, app: &mut App) {
     match key.code {
-        KeyCode::Char(_) => app.change_screen(app.get_previous_screen()),
+        KeyCode::Char('y') => app.run_pending_action(),
+        KeyCode::Char('n') | KeyCode::Esc => app.cancel_pending_action(),
         _ => (),
     }
 }
 
-async fn main_behavior(key: &KeyEvent, app: &mut App) {
+fn profiles_behaviour(key: &KeyEvent, app: &mut App) {
     match key.code {
-        KeyCode::Char('q') => app.change_screen(Screens::Exiting),
-        KeyCode::Char('r') => app.change_screen(Screens::GlobalResultMain),
-        KeyCode::Char('s') => app.start_typing(),
-        KeyCode::Char('R') => app.delete_json(),
-        KeyCode::Char('t') => {
-            if let Err(err) = app.get_new_texts().await {
-                app.alert(format!(
-                    "You need to add the file with yor api key in the folder of the project, with the name \"api_key.txt\" if you want to use this feature.\n\n{}",
-                    err.to_string(),
-                ));
-            }
-        }
+        KeyCode::Esc => app.change_screen(Screens::Main),
+        KeyCode::Up => app.select_previous_profile(),
+        KeyCode::Down => app.select_next_profile(),
+        KeyCode::Enter => app.choose_profile(),
+        KeyCode::Char('n') => app.start_name_input(NameAction::New),
+        KeyCode::Char('r') => app.start_name_input(NameAction::Rename),
+        KeyCode::Char('c') => app.start_name_input(NameAction::Copy),
+        KeyCode::Char('d') => app.delete_selected_profile(),
+        _ => (),
+    }
+}
+
+fn profile_name_behaviour(key: &KeyEvent, app: &mut App) {
+    match key.code {
+        KeyCode::Esc => app.change_screen(Screens::Profiles),
+        KeyCode::Enter => app.submit_name(),
+        KeyCode::Backspace => app.pop_name_char(),
+        KeyCode::Char(ch) => app.push_name_char(ch),
+        _ => (),
+    }
+}
+
+fn replay_list_behaviour(key: &KeyEvent, app: &mut App) {
+    match key.code {
+        KeyCode::Esc => app.change_screen(Screens::Main),
+        KeyCode::Up => app.select_previous_replay(),
+        KeyCode::Down => app.select_next_replay(),
+        KeyCode::Enter => app.start_replay(),
+        KeyCode::Char('g') => app.race_replay(),
+        _ => (),
+    }
+}
+
+fn replay_behaviour(key: &KeyEvent, app: &mut App) {
+    if key.code == KeyCode::Esc {
+        app.stop_replay();
+        return;
+    }
+    let Some(replay) = app.get_replay_mut() else {
+        return;
+    };
+    match key.code {
+        KeyCode::Char(' ') => replay.toggle_pause(),
+        KeyCode::Char('+') | KeyCode::Char('=') => replay.faster(),
+        KeyCode::Char('-') => replay.slower(),
+        KeyCode::Right => replay.seek_forward(),
+        KeyCode::Left => replay.seek_back(),
+        KeyCode::Char('.') => replay.step_forward(),
+        KeyCode::Char(',') => replay.step_back(),
+        _ => (),
+    }
+}
+
+fn ngrams_behaviour(key: &KeyEvent, app: &mut App) {
+    match key.code {
+        KeyCode::Esc => app.change_screen(Screens::Main),
+        KeyCode::Char('2') => app.show_ngrams(2),
+        KeyCode::Char('3') => app.show_ngrams(3),
+        _ => (),
+    }
+}
+
+fn progress_behaviour(key: &KeyEvent, app: &mut App) {
+    match key.code {
+        KeyCode::Esc => app.close_progress(),
+        KeyCode::Char('1') => app.set_progress_range(ProgressRange::Week),
+        KeyCode::Char('2') => app.set_progress_range(ProgressRange::Month),
+        KeyCode::Char('3') => app.set_progress_range(ProgressRange::All),
+        _ => (),
+    }
+}
+
+fn confusions_behaviour(key: &KeyEvent, app: &mut App) {
+    match key.code {
+        KeyCode::Esc => app.change_screen(Screens::Main),
+        KeyCode::Up => app.scroll_confusions_up(),
+        KeyCode::Down => app.scroll_confusions_down(),
+        KeyCode::Char('s') => app.change_confusions_sort(),
+        KeyCode::Char('e') => app.export_confusions(),
+        _ => (),
+    }
+}
+
+fn main_behavior(key: &KeyEvent, app: &mut App) {
+    let keys = app.get_config().keys;
+    match key.code {
+        KeyCode::Char(ch) if ch == keys.quit => app.change_screen(Screens::Exiting),
+        KeyCode::Char(ch) if ch == keys.results => app.change_screen(Screens::GlobalResultMain),
+        KeyCode::Char(ch) if ch == keys.start => app.start_typing(),
+        KeyCode::Char(ch) if ch == keys.delete_results => app.ask_reset_results(),
+        KeyCode::Char(ch) if ch == keys.undo_reset => app.undo_reset(),
+        KeyCode::Char(ch) if ch == keys.profiles => app.open_profiles(),
+        KeyCode::Char(ch) if ch == keys.replay => app.open_replays(),
+        KeyCode::Char(ch) if ch == keys.ngrams => app.open_ngrams(),
+        KeyCode::Char(ch) if ch == keys.progress => app.open_progress(None),
+        KeyCode::Char(ch) if ch == keys.confusions => app.open_confusions(),
+        KeyCode::Char(ch) if ch == keys.mode => app.change_mode(),
+        KeyCode::Char(ch) if ch == keys.free => app.toggle_free(),
+        KeyCode::Char(ch) if ch == keys.challenge => app.change_challenge(),
+        KeyCode::Char(ch) if ch == keys.new_texts => app.fetch_new_texts(),
         _ => (),
     }
 }
@@ -132,15 +346,16 @@ fn typing_behavior(key: &KeyEvent, app: &mut App) {
         KeyCode::Esc => app.change_screen(Screens::Main),
         // reload the typing letters
         KeyCode::Tab => app.reload_typing(),
-        KeyCode::Char(_) => {
-            // if there are the next letter
-            if let Some(_letter) = app.guess() {
-                // else if there are no more letters in the word we end to typing
-            } else {
-                // make the end of the typing
-                app.change_screen(Screens::TypingResult);
-            }
+        // the terminals send ctrl+backspace as ctrl+h, ctrl+w deletes the word in the shells too
+        KeyCode::Backspace | KeyCode::Char('h') | KeyCode::Char('w')
+            if key.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            app.delete_word()
         }
+        KeyCode::Backspace => app.backspace(),
+        // the line breaks of the paragraphs
+        KeyCode::Enter => app.guess_line_break(),
+        KeyCode::Char(_) => app.guess(),
         _ => (),
     }
 }
@@ -151,6 +366,14 @@ fn end_typing_behaviour(key: &KeyEvent, app: &mut App) {
         _ => (),
     }
 }
+fn failed_typing_behaviour(key: &KeyEvent, app: &mut App) {
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => app.change_screen(Screens::Main),
+        KeyCode::Char('r') => app.retry_typing(),
+        KeyCode::Char('c') => app.start_typing(),
+        _ => (),
+    }
+}
 fn exiting_behavior(key: &KeyEvent, app: &mut App) -> bool {
     loop {
         match key.code {
@@ -173,6 +396,7 @@ fn global_res_behavior(key: &KeyEvent, app: &mut App) {
 fn letter_res_behavior(key: &KeyEvent, app: &mut App) {
     match key.code {
         KeyCode::Esc => app.change_screen(Screens::GlobalResultMain),
+        KeyCode::Enter => app.open_progress(Some(app.get_pressed_letter())),
         KeyCode::Char(ch) => {
             app.set_key_pressed(ch);
             app.change_screen(Screens::LetterResult)
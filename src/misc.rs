@@ -1,11 +1,6 @@
-use std::{
-    fs::{self, File},
-    hash::{BuildHasher, DefaultHasher},
-    io::{self, Read},
-};
+use std::hash::{BuildHasher, DefaultHasher};
 
 use chatgpt::{client::ChatGPT, types::CompletionResponse};
-use ratatui::style::Color;
 
 #[derive(Default)]
 pub struct MyHasher {}
@@ -24,31 +19,14 @@ impl BuildHasher for MyHasher {
     }
 }
 
-pub fn get_color_by_accuracy(accuracy: f64) -> Color {
-    match accuracy {
-        perc if perc == 101.0 => Color::Yellow,
-        perc if perc == 0.0 => Color::Reset,
-        perc if perc >= 80.0 => Color::Green,
-        perc if perc >= 50.0 => Color::Blue,
-        perc if perc <= 50.0 => Color::Red,
-        _ => Color::Reset,
-    }
-}
-
-pub async fn get_chatgpt_words() -> Result<String, chatgpt::err::Error> {
-    // Getting the API key here
-    let key = fs::read_to_string("api_key.txt")?;
-    let key = key[..key.len() - 1].to_string();
-
+pub async fn get_chatgpt_words(key: String, prompt: &str) -> Result<String, chatgpt::err::Error> {
     // Creating a new ChatGPT client.
     // Note that it requires an API key, and uses
     // tokens from your OpenAI API account balance.
     let client = ChatGPT::new(key)?;
 
     // Sending a message and getting the completion
-    let response: CompletionResponse = client
-        .send_message("Write me 10 sentences, separated with newline and are good for blind typing test, but not the default examples. Write nothing else but the sentences without the numbers")
-        .await?;
+    let response: CompletionResponse = client.send_message(prompt).await?;
 
     Ok(response.message().content.to_string())
 }
@@ -0,0 +1,442 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+
+use crate::{
+    app::{
+        history::{self, SessionRecord},
+        typing_screen::JSONResults,
+    },
+    cli::Mode,
+    config::{Backend, Weighting},
+    misc::get_default_sentences,
+};
+
+use self::json::JsonStore;
+#[cfg(feature = "sqlite")]
+use self::sqlite::SqliteStore;
+
+pub mod json;
+pub mod migrations;
+pub mod profiles;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+const APP_DIR: &str = "blind_typing";
+const TEXTS_FILE: &str = "texts.txt";
+const RESULTS_FILE: &str = "history.jsonl";
+#[cfg(feature = "sqlite")]
+const SQLITE_RESULTS_FILE: &str = "results.sqlite3";
+// the merged results of the older versions, before every test was stored in the history
+const LEGACY_RESULTS_FILE: &str = "results.json";
+// the deleted results are kept here, so the reset can be undone
+const RESETS_DIR: &str = "resets";
+// the statistics, that are exported for the other tools
+const EXPORTS_DIR: &str = "exports";
+const API_KEY_FILE: &str = "api_key.txt";
+// the files of the older versions in the working directory are looked for only before it exists
+const CWD_MIGRATED_MARKER: &str = ".cwd_migrated";
+const CONFIG_FILE: &str = "config.toml";
+// how many older versions of every written file are kept
+const BACKUPS: usize = 3;
+
+// the place where the finished tests are stored
+pub trait ResultsStore {
+    fn path(&self) -> &Path;
+
+    // all the finished tests, the oldest at first
+    fn read_history(&self) -> Result<Vec<SessionRecord>, io::Error>;
+
+    fn append_session(&self, record: &SessionRecord) -> Result<(), io::Error>;
+
+    fn append_sessions(&self, records: &[SessionRecord]) -> Result<(), io::Error> {
+        for record in records {
+            self.append_session(record)?;
+        }
+        Ok(())
+    }
+
+    // deletes all the stored tests
+    fn clear(&self) -> Result<(), io::Error>;
+
+    // rewrites the tests of the older versions in the newest format
+    fn upgrade(&self) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    // replaces the broken results with the newest backup, that is not broken
+    fn restore_backup(&self) -> Result<(), io::Error> {
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "There are no backups for this kind of storage",
+        ))
+    }
+
+    // the results of all the tests of the mode together, the timed tests and the sentences are
+    // not mixed
+    fn read_results(&self, weighting: Weighting, mode: Mode) -> Result<JSONResults, io::Error> {
+        let mut history = self.read_history()?;
+        history.retain(|record| record.mode == mode);
+        history::get_global_results(&history, weighting).ok_or(io::Error::new(
+            ErrorKind::NotFound,
+            format!("There are no results of the {} mode yet", mode.as_str()),
+        ))
+    }
+}
+
+// all the files of the app are read and written only through this struct, so the results stay
+// in one place, no matter from which directory the app was started
+pub struct Storage {
+    profile: String,
+    texts: PathBuf,
+    results: Box<dyn ResultsStore>,
+    // the merged results of the older versions, they are migrated after the history is checked
+    legacy_results: PathBuf,
+    resets: PathBuf,
+    exports: PathBuf,
+    api_key: PathBuf,
+}
+
+impl Storage {
+    // the paths from the command line replace the paths in the directory of the profile
+    pub fn new(
+        profile: &str,
+        texts: Option<PathBuf>,
+        results: Option<PathBuf>,
+        backend: Backend,
+    ) -> Result<Storage, io::Error> {
+        profiles::validate_name(profile)?;
+        let config_dir = xdg_dir("XDG_CONFIG_HOME", ".config")?;
+        fs::create_dir_all(xdg_dir("XDG_DATA_HOME", ".local/share")?)?;
+        fs::create_dir_all(&config_dir)?;
+        profiles::migrate_to_default()?;
+
+        let data_dir = profiles::data_dir(profile)?;
+        fs::create_dir_all(&data_dir)?;
+
+        // the older versions had no profiles, so their files belong to the default profile
+        if profile == profiles::DEFAULT_PROFILE {
+            migrate_from_cwd(&data_dir, &config_dir)?;
+        }
+
+        let results: Box<dyn ResultsStore> = match backend {
+            Backend::Json => Box::new(JsonStore::open(
+                results.unwrap_or(data_dir.join(RESULTS_FILE)),
+            )?),
+            #[cfg(feature = "sqlite")]
+            Backend::Sqlite => Box::new(SqliteStore::open(
+                results.unwrap_or(data_dir.join(SQLITE_RESULTS_FILE)),
+            )?),
+        };
+
+        let storage = Storage {
+            profile: profile.to_string(),
+            texts: texts.unwrap_or(data_dir.join(TEXTS_FILE)),
+            results,
+            legacy_results: data_dir.join(LEGACY_RESULTS_FILE),
+            resets: data_dir.join(RESETS_DIR),
+            exports: data_dir.join(EXPORTS_DIR),
+            api_key: config_dir.join(API_KEY_FILE),
+        };
+        // the broken results are not upgraded, the app offers to restore them on the start
+        match storage.results.upgrade() {
+            Err(err) if err.kind() != ErrorKind::InvalidData => return Err(err),
+            _ => (),
+        }
+        Ok(storage)
+    }
+
+    // the merged results of the older versions become the first test of the history, because it
+    // has the time of the old file, the old file is renamed, so it is not migrated twice
+    pub fn migrate_legacy_results(&self) -> Result<(), io::Error> {
+        let legacy = &self.legacy_results;
+        if get_migrated_path(legacy).exists() {
+            return Ok(());
+        }
+        let text = match fs::read_to_string(legacy) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        // the deleted results were stored as the empty file
+        if !text.trim().is_empty() {
+            let timestamp = fs::metadata(legacy)?.modified()?.into();
+            let record = serde_json::from_str(&text)
+                .map_err(io::Error::from)
+                .and_then(|value| migrations::migrate_legacy(value, timestamp))
+                .map_err(|err| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Cannot migrate {}: {err}", legacy.display()),
+                    )
+                })?;
+            self.results.append_session(&record)?;
+        }
+
+        fs::rename(legacy, get_migrated_path(legacy))
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    pub fn results(&self) -> &dyn ResultsStore {
+        self.results.as_ref()
+    }
+
+    // the tests are saved to the timestamped file before they are deleted, returns this file
+    pub fn reset_results(&self) -> Result<PathBuf, io::Error> {
+        let history = self.results.read_history()?;
+
+        fs::create_dir_all(&self.resets)?;
+        let backup = self.resets.join(format!(
+            "history-{}.jsonl",
+            Local::now().format("%Y-%m-%d_%H-%M-%S%.3f")
+        ));
+        write_atomic(&backup, json::to_lines(&history)?.as_bytes())?;
+
+        self.results.clear()?;
+        Ok(backup)
+    }
+
+    // the tests of the last reset are added to the results again, the tests, that were typed
+    // after the reset, are kept, returns the amount of the restored tests
+    pub fn undo_reset(&self) -> Result<usize, io::Error> {
+        let backup = self.get_resets()?.pop().ok_or(io::Error::new(
+            ErrorKind::NotFound,
+            "There is no reset, that can be undone",
+        ))?;
+        let history = json::read_records(&backup)?;
+
+        self.results.append_sessions(&history)?;
+        fs::remove_file(backup)?;
+        Ok(history.len())
+    }
+
+    // the backups of the resets, the newest at last
+    fn get_resets(&self) -> Result<Vec<PathBuf>, io::Error> {
+        let entries = match fs::read_dir(&self.resets) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+
+        let mut resets = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "jsonl") {
+                resets.push(path);
+            }
+        }
+        // the names start with the time of the reset
+        resets.sort();
+        Ok(resets)
+    }
+
+    pub fn api_key_path(&self) -> &Path {
+        &self.api_key
+    }
+
+    pub fn texts_path(&self) -> &Path {
+        &self.texts
+    }
+
+    // every export gets its own file with the time in the name, returns its path
+    pub fn write_export(
+        &self,
+        name: &str,
+        extension: &str,
+        contents: &str,
+    ) -> Result<PathBuf, io::Error> {
+        fs::create_dir_all(&self.exports)?;
+        let path = self.exports.join(format!(
+            "{name}-{}.{extension}",
+            Local::now().format("%Y-%m-%d_%H-%M-%S")
+        ));
+        write_atomic(&path, contents.as_bytes())?;
+        Ok(path)
+    }
+
+    // returns the texts, if there are no texts file it will be created with the default texts
+    pub fn read_texts(&self) -> Result<String, io::Error> {
+        match fs::read_to_string(&self.texts) {
+            Ok(texts) => Ok(texts),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let sentences = get_default_sentences();
+                self.write_texts(&sentences)?;
+                Ok(sentences)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn write_texts(&self, texts: &str) -> Result<(), io::Error> {
+        write_atomic(&self.texts, texts.as_bytes())
+    }
+
+    pub fn read_api_key(&self) -> Result<String, io::Error> {
+        let key = fs::read_to_string(&self.api_key)?;
+        Ok(key.trim().to_string())
+    }
+}
+
+// the file is written to the temporary file at first and then renamed, so after a crash there is
+// either the old or the new file, but never a half written one
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), io::Error> {
+    let tmp = with_suffix(path, "tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+
+    rotate_backups(path)?;
+    fs::rename(&tmp, path)
+}
+
+// the history is only appended, so it is backed up once on the start instead of on every write,
+// the file, that didn't change since the last backup, isn't backed up again
+fn backup_on_start(path: &Path) -> Result<(), io::Error> {
+    let last = backup_path(path, 1);
+    if !path.exists() || (last.exists() && fs::read(path)? == fs::read(&last)?) {
+        return Ok(());
+    }
+    rotate_backups(path)
+}
+
+// the current file becomes the backup 1, the backup 1 becomes the backup 2 and so on
+fn rotate_backups(path: &Path) -> Result<(), io::Error> {
+    if !path.exists() {
+        return Ok(());
+    }
+    for i in (1..BACKUPS).rev() {
+        let backup = backup_path(path, i);
+        if backup.exists() {
+            fs::rename(&backup, backup_path(path, i + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+// the existing backups of the file, the newest at first
+fn get_backups(path: &Path) -> Vec<PathBuf> {
+    (1..=BACKUPS)
+        .map(|i| backup_path(path, i))
+        .filter(|backup| backup.exists())
+        .collect()
+}
+
+fn backup_path(path: &Path, i: usize) -> PathBuf {
+    with_suffix(path, &format!("bak.{i}"))
+}
+
+// "results.json" with the suffix "tmp" is "results.json.tmp"
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// the content of the config file of the profile or of the main config file, if the profile has
+// no own one
+pub fn read_config(profile: &str) -> Result<Option<String>, io::Error> {
+    profiles::validate_name(profile)?;
+    for dir in [
+        profiles::config_dir(profile)?,
+        xdg_dir("XDG_CONFIG_HOME", ".config")?,
+    ] {
+        match fs::read_to_string(dir.join(CONFIG_FILE)) {
+            Ok(config) => return Ok(Some(config)),
+            Err(err) if err.kind() == ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(None)
+}
+
+// the directory of the app in $var or in $HOME/fallback, if $var is not set
+fn xdg_dir(var: &str, fallback: &str) -> Result<PathBuf, io::Error> {
+    let base = match env::var_os(var).map(PathBuf::from) {
+        // relative paths are invalid by the xdg specification and must be ignored
+        Some(dir) if dir.is_absolute() => dir,
+        _ => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(fallback),
+            None => {
+                return Err(io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("Neither ${var} nor $HOME are set"),
+                ))
+            }
+        },
+    };
+    Ok(base.join(APP_DIR))
+}
+
+// the older versions stored their files in the directory, where the app was started, they are
+// copied once on the first run, so the files of the other apps in the later working directories
+// are never touched, the files in the working directory stay as they are
+fn migrate_from_cwd(data_dir: &Path, config_dir: &Path) -> Result<(), io::Error> {
+    let marker = xdg_dir("XDG_DATA_HOME", ".local/share")?.join(CWD_MIGRATED_MARKER);
+    if marker.exists() {
+        return Ok(());
+    }
+
+    copy_from_cwd(TEXTS_FILE, &data_dir.join(TEXTS_FILE), is_legacy_texts)?;
+    // the results, that were already migrated, are not added to the history again
+    let legacy_results = data_dir.join(LEGACY_RESULTS_FILE);
+    if !get_migrated_path(&legacy_results).exists() {
+        copy_from_cwd(LEGACY_RESULTS_FILE, &legacy_results, is_legacy_results)?;
+    }
+    copy_from_cwd(
+        API_KEY_FILE,
+        &config_dir.join(API_KEY_FILE),
+        is_legacy_api_key,
+    )?;
+
+    File::create(marker)?;
+    Ok(())
+}
+
+// copies the file from the working directory, if there is no file at the new place yet and the
+// file has the format of the older versions
+fn copy_from_cwd(filename: &str, to: &Path, is_legacy: fn(&str) -> bool) -> Result<(), io::Error> {
+    let from = Path::new(filename);
+    if to.exists() || !from.is_file() {
+        return Ok(());
+    }
+    // the file, that can't be read, isn't a file of the older versions
+    match fs::read_to_string(from) {
+        Ok(text) if is_legacy(&text) => fs::copy(from, to).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+// "results.json" becomes "results.json.migrated" after its migration
+fn get_migrated_path(legacy: &Path) -> PathBuf {
+    with_suffix(legacy, "migrated")
+}
+
+// one text per line
+fn is_legacy_texts(text: &str) -> bool {
+    text.lines().any(|line| !line.trim().is_empty())
+}
+
+// the merged results with the wpm, the accuracy and the info of every letter
+fn is_legacy_results(text: &str) -> bool {
+    serde_json::from_str(text)
+        .map_err(io::Error::from)
+        .and_then(|value| migrations::migrate_legacy(value, Local::now()))
+        .is_ok()
+}
+
+// only the key on one line
+fn is_legacy_api_key(text: &str) -> bool {
+    let key = text.trim();
+    !key.is_empty() && !key.contains(char::is_whitespace)
+}
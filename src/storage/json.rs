@@ -0,0 +1,190 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::app::history::SessionRecord;
+
+use super::{
+    backup_on_start, get_backups,
+    migrations::{self, SCHEMA_VERSION},
+    with_suffix, write_atomic, ResultsStore,
+};
+
+// every test is one line of json in the file
+pub struct JsonStore {
+    path: PathBuf,
+}
+
+impl JsonStore {
+    // the history is backed up and its cut last line is repaired on the start
+    pub fn open(path: PathBuf) -> Result<JsonStore, io::Error> {
+        backup_on_start(&path)?;
+        repair_last_line(&path)?;
+        Ok(JsonStore { path })
+    }
+}
+
+impl ResultsStore for JsonStore {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read_history(&self) -> Result<Vec<SessionRecord>, io::Error> {
+        let mut history = read_records(&self.path)?;
+        // the migrated tests are appended later, than they were typed
+        history.sort_by_key(|record| record.timestamp);
+        Ok(history)
+    }
+
+    fn append_session(&self, record: &SessionRecord) -> Result<(), io::Error> {
+        self.append_sessions(std::slice::from_ref(record))
+    }
+
+    // the tests are appended to the end of the file, so the older tests are never changed and
+    // the saving doesn't take longer with the bigger history
+    fn append_sessions(&self, records: &[SessionRecord]) -> Result<(), io::Error> {
+        let lines = to_lines(records)?;
+        // the new test must not be glued to the last line, if its line break is missing
+        let separator = match ends_with_line_break(&self.path)? {
+            true => "",
+            false => "\n",
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(format!("{separator}{lines}").as_bytes())?;
+        file.sync_all()
+    }
+
+    fn upgrade(&self) -> Result<(), io::Error> {
+        let lines = read_lines(&self.path)?;
+        let mut is_old = false;
+        for (_, line) in lines.iter() {
+            is_old |= migrations::get_version(line)? < SCHEMA_VERSION;
+        }
+        if !is_old {
+            return Ok(());
+        }
+
+        let history = to_lines(&read_records(&self.path)?)?;
+        // the old file stays in the backups
+        write_atomic(&self.path, history.as_bytes())
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        write_atomic(&self.path, "".as_bytes())
+    }
+
+    fn restore_backup(&self) -> Result<(), io::Error> {
+        let backup = get_backups(&self.path)
+            .into_iter()
+            .find(|backup| read_records(backup).is_ok())
+            .ok_or(io::Error::new(
+                ErrorKind::NotFound,
+                "There is no backup, that is not broken",
+            ))?;
+
+        // the broken file is kept, maybe it can be repaired by hand
+        if self.path.exists() {
+            fs::rename(&self.path, with_suffix(&self.path, "broken"))?;
+        }
+        fs::copy(backup, &self.path)?;
+        Ok(())
+    }
+}
+
+fn ends_with_line_break(path: &Path) -> Result<bool, io::Error> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(true),
+        Err(err) => return Err(err),
+    };
+    if file.metadata()?.len() == 0 {
+        return Ok(true);
+    }
+    let mut last = [0];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+// the crash while appending can cut the last test, it can't be read anymore, so it is removed
+// and the history stays readable, the whole file is still in the backup of the start
+fn repair_last_line(path: &Path) -> Result<(), io::Error> {
+    if ends_with_line_break(path)? {
+        return Ok(());
+    }
+    let history = fs::read(path)?;
+    let start = history
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |i| i + 1);
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    match serde_json::from_slice::<Value>(&history[start..]) {
+        // only the line break is missing
+        Ok(_) => {
+            file.seek(SeekFrom::End(0))?;
+            file.write_all(b"\n")?;
+        }
+        Err(_) => file.set_len(start as u64)?,
+    }
+    file.sync_all()
+}
+
+// every test as one line of json
+pub fn to_lines(records: &[SessionRecord]) -> Result<String, io::Error> {
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+    Ok(lines)
+}
+
+// the tests of the older versions are migrated to the newest one
+pub fn read_records(path: &Path) -> Result<Vec<SessionRecord>, io::Error> {
+    let mut history = vec![];
+    for (line_i, line) in read_lines(path)? {
+        let record = migrations::migrate(line).map_err(|err| match err.kind() {
+            ErrorKind::InvalidData => io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Line {line_i} of the history is broken: {err}"),
+            ),
+            _ => err,
+        })?;
+        history.push(record);
+    }
+    Ok(history)
+}
+
+// every not empty line as json with its number
+fn read_lines(path: &Path) -> Result<Vec<(usize, Value)>, io::Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut lines = vec![];
+    for (line_i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = serde_json::from_str(&line).map_err(|err| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Line {} of the history is broken: {err}", line_i + 1),
+            )
+        })?;
+        lines.push((line_i + 1, value));
+    }
+    Ok(lines)
+}
@@ -0,0 +1,269 @@
+use std::io::{self, ErrorKind};
+
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+
+use crate::app::{
+    history::{get_accuracy, Keystroke, SessionRecord},
+    typing_screen::JSONResults,
+};
+
+// the version of the stored tests, it must be increased with every change of SessionRecord, that
+// can't be read from the older files, and the migration to it must be added to MIGRATIONS
+pub const SCHEMA_VERSION: u32 = 4;
+
+// MIGRATIONS[n] changes the version n to the version n + 1
+//
+// 0 - the merged results.json of the versions without the history
+// 1 - the history without schema_version, the older lines have no chars, presses and duration
+// 2 - wpm was the amount of the words of the text per minute, there were no raw, net wpm and cpm
+// 3 - the letters had no latencies
+const MIGRATIONS: [fn(Value) -> Result<Value, io::Error>; SCHEMA_VERSION as usize] =
+    [from_v0_to_v1, from_v1_to_v2, from_v2_to_v3, from_v3_to_v4];
+
+// reads the stored test of any older version
+pub fn migrate(value: Value) -> Result<SessionRecord, io::Error> {
+    let version = get_version(&value)?;
+    migrate_from(value, version)
+}
+
+// reads the merged results.json of the older versions, its time is the time of the file
+pub fn migrate_legacy(
+    mut value: Value,
+    timestamp: DateTime<Local>,
+) -> Result<SessionRecord, io::Error> {
+    value["timestamp"] = json!(timestamp);
+    migrate_from(value, 0)
+}
+
+// the version of the stored test, the versions 0 and 1 had no field for it
+pub fn get_version(value: &Value) -> Result<u32, io::Error> {
+    match value.get("schema_version") {
+        Some(version) => version
+            .as_u64()
+            .map(|version| version as u32)
+            .ok_or(invalid("schema_version must be a number")),
+        None if value.get("timestamp").is_some() => Ok(1),
+        None => Ok(0),
+    }
+}
+
+fn migrate_from(mut value: Value, version: u32) -> Result<SessionRecord, io::Error> {
+    if version > SCHEMA_VERSION {
+        return Err(io::Error::new(
+            ErrorKind::Unsupported,
+            format!(
+                "The results are from a newer version of the app (schema {version}, this app knows only {SCHEMA_VERSION})"
+            ),
+        ));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        value = migration(value)?;
+    }
+    serde_json::from_value(value).map_err(|err| invalid(&err.to_string()))
+}
+
+// the merged results become one test without the text, the time of typing is estimated from the
+// wpm with the words of 5 characters
+fn from_v0_to_v1(value: Value) -> Result<Value, io::Error> {
+    let timestamp = value
+        .get("timestamp")
+        .cloned()
+        .unwrap_or(json!(Local::now()));
+    let results: JSONResults =
+        serde_json::from_value(value).map_err(|err| invalid(&err.to_string()))?;
+
+    let (chars, presses) = results.get_presses();
+    let duration_ms = estimate_duration(chars as f64 / 5.0, results.wpm);
+
+    let mut record = serde_json::to_value(&results)?;
+    record["total_accuracy"] = json!(get_accuracy(chars, presses));
+    record["timestamp"] = timestamp;
+    record["text"] = json!("");
+    record["mode"] = json!("sentence");
+    record["chars"] = json!(chars);
+    record["presses"] = json!(presses);
+    record["duration_ms"] = json!(duration_ms);
+    Ok(record)
+}
+
+// the first lines of the history had no chars, presses and duration, they are calculated from the
+// text, the letters info and the wpm
+fn from_v1_to_v2(mut value: Value) -> Result<Value, io::Error> {
+    if value.get("chars").is_none() {
+        let text = value["text"].as_str().unwrap_or_default().to_string();
+        let wpm = value["wpm"].as_f64().unwrap_or_default();
+        let results: JSONResults =
+            serde_json::from_value(value.clone()).map_err(|err| invalid(&err.to_string()))?;
+
+        value["chars"] = json!(text.chars().count());
+        value["presses"] = json!(results.get_presses().1);
+        value["duration_ms"] = json!(estimate_duration(
+            text.split_whitespace().count() as f64,
+            wpm
+        ));
+    }
+    value["schema_version"] = json!(2);
+    Ok(value)
+}
+
+// the speeds are calculated with the words of 5 characters from the stored amounts
+fn from_v2_to_v3(mut value: Value) -> Result<Value, io::Error> {
+    let mut results: JSONResults =
+        serde_json::from_value(value.clone()).map_err(|err| invalid(&err.to_string()))?;
+    results.set_speeds(
+        value["chars"].as_u64().unwrap_or_default() as usize,
+        value["presses"].as_u64().unwrap_or_default() as usize,
+        value["duration_ms"].as_i64().unwrap_or_default(),
+    );
+
+    value["wpm"] = json!(results.wpm);
+    value["raw_wpm"] = json!(results.raw_wpm);
+    value["net_wpm"] = json!(results.net_wpm);
+    value["cpm"] = json!(results.cpm);
+    value["schema_version"] = json!(3);
+    Ok(value)
+}
+
+// the latencies are calculated from the keystrokes, the tests without them have no latencies
+fn from_v3_to_v4(mut value: Value) -> Result<Value, io::Error> {
+    let mut results: JSONResults =
+        serde_json::from_value(value.clone()).map_err(|err| invalid(&err.to_string()))?;
+    let keystrokes: Vec<Keystroke> =
+        serde_json::from_value(value.get("keystrokes").cloned().unwrap_or(json!([])))
+            .map_err(|err| invalid(&err.to_string()))?;
+    results.set_latencies(&keystrokes);
+
+    value["letters_info"] = serde_json::to_value(&results.letters_info)?;
+    value["schema_version"] = json!(4);
+    Ok(value)
+}
+
+fn estimate_duration(words: f64, wpm: f64) -> i64 {
+    if wpm > 0.0 {
+        (words / wpm * 60000.0).round() as i64
+    } else {
+        0
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESULTS_V0: &str = include_str!("../../tests/fixtures/results_v0.json");
+    const HISTORY_V1: &str = include_str!("../../tests/fixtures/history_v1.jsonl");
+
+    fn get_v1_lines() -> Vec<Value> {
+        HISTORY_V1
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn versions_are_detected() {
+        let v0: Value = serde_json::from_str(RESULTS_V0).unwrap();
+        assert_eq!(get_version(&v0).unwrap(), 0);
+        for line in get_v1_lines() {
+            assert_eq!(get_version(&line).unwrap(), 1);
+        }
+        assert_eq!(
+            get_version(&json!({"schema_version": SCHEMA_VERSION})).unwrap(),
+            SCHEMA_VERSION
+        );
+    }
+
+    #[test]
+    fn legacy_results_are_migrated() {
+        let v0: Value = serde_json::from_str(RESULTS_V0).unwrap();
+        let timestamp = Local::now();
+        let record = migrate_legacy(v0, timestamp).unwrap();
+
+        assert_eq!(record.schema_version, SCHEMA_VERSION);
+        assert_eq!(record.timestamp, timestamp);
+        assert_eq!(record.text, "");
+        assert_eq!(record.chars, 413);
+        assert_eq!(record.presses, 472);
+        assert_eq!(record.results.total_accuracy, 87.5);
+        assert_eq!(record.results.letters_info.len(), 30);
+        // 413 / 5 words with 52.19 wpm
+        assert_eq!(record.duration_ms, 94969);
+    }
+
+    #[test]
+    fn history_without_version_is_migrated() {
+        let records = get_v1_lines()
+            .into_iter()
+            .map(migrate)
+            .collect::<Result<Vec<SessionRecord>, io::Error>>()
+            .unwrap();
+
+        // the line without chars, presses and duration
+        assert_eq!(records[0].schema_version, SCHEMA_VERSION);
+        assert_eq!(records[0].chars, 42);
+        assert_eq!(records[0].presses, 44);
+        // 9 words with 48.3 wpm
+        assert_eq!(records[0].duration_ms, 11180);
+        // the older tests have no keystrokes
+        assert!(records[0].keystrokes.is_empty());
+
+        // the line with them stays the same
+        assert_eq!(records[1].chars, 36);
+        assert_eq!(records[1].presses, 38);
+        assert_eq!(records[1].duration_ms, 9120);
+    }
+
+    #[test]
+    fn speeds_are_calculated_with_5_char_words() {
+        let record = migrate(get_v1_lines().remove(1)).unwrap();
+
+        // 36 chars and 38 presses in 9.12 seconds
+        assert_eq!(record.results.wpm, 47.4);
+        assert_eq!(record.results.raw_wpm, 50.0);
+        // 2 errors in 0.152 minutes
+        assert_eq!(record.results.net_wpm, 34.2);
+        assert_eq!(record.results.cpm, 236.8);
+    }
+
+    #[test]
+    fn latencies_are_calculated_from_keystrokes() {
+        let mut line = get_v1_lines().remove(1);
+        line["keystrokes"] = json!([
+            {"expected": "A", "pressed": "A", "offset_ms": 0, "correct": true},
+            {"expected": " ", "pressed": "x", "offset_ms": 100, "correct": false},
+            {"expected": " ", "pressed": " ", "offset_ms": 250, "correct": true},
+            {"expected": "b", "pressed": "b", "offset_ms": 400, "correct": true},
+        ]);
+        let letters = migrate(line).unwrap().results.letters_info;
+
+        // the first letter has no previous keystroke
+        assert!(letters[&'A'].latencies_ms.is_empty());
+        // the wrong keystroke belongs to the time of the letter
+        assert_eq!(letters[&' '].latencies_ms, vec![250]);
+        assert_eq!(letters[&'b'].latencies_ms, vec![150]);
+    }
+
+    #[test]
+    fn current_version_is_not_changed() {
+        let record = migrate(get_v1_lines().remove(1)).unwrap();
+        let line = serde_json::to_value(&record).unwrap();
+        let again = migrate(line.clone()).unwrap();
+
+        assert_eq!(serde_json::to_value(&again).unwrap(), line);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let mut line = get_v1_lines().remove(1);
+        line["schema_version"] = json!(SCHEMA_VERSION + 1);
+
+        let err = migrate(line).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+}
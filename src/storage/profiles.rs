@@ -0,0 +1,148 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use super::xdg_dir;
+
+pub const DEFAULT_PROFILE: &str = "default";
+const PROFILES_DIR: &str = "profiles";
+
+// the directory with the results and the texts of the profile
+pub fn data_dir(profile: &str) -> Result<PathBuf, io::Error> {
+    Ok(xdg_dir("XDG_DATA_HOME", ".local/share")?
+        .join(PROFILES_DIR)
+        .join(profile))
+}
+
+// the directory with the config of the profile, the api key stays in the main config directory
+pub fn config_dir(profile: &str) -> Result<PathBuf, io::Error> {
+    Ok(xdg_dir("XDG_CONFIG_HOME", ".config")?
+        .join(PROFILES_DIR)
+        .join(profile))
+}
+
+// the names of all the profiles sorted by the name
+pub fn list_profiles() -> Result<Vec<String>, io::Error> {
+    let dir = xdg_dir("XDG_DATA_HOME", ".local/share")?.join(PROFILES_DIR);
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut names = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            if let Some(name) = entry.file_name().to_str() {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub fn create_profile(name: &str) -> Result<(), io::Error> {
+    check_new_name(name)?;
+    fs::create_dir_all(data_dir(name)?)
+}
+
+pub fn rename_profile(from: &str, to: &str) -> Result<(), io::Error> {
+    check_new_name(to)?;
+    fs::rename(data_dir(from)?, data_dir(to)?)?;
+
+    let config = config_dir(from)?;
+    if config.exists() {
+        fs::create_dir_all(config_dir(to)?.parent().unwrap())?;
+        fs::rename(config, config_dir(to)?)?;
+    }
+    Ok(())
+}
+
+// the new profile gets the results, the texts and the settings of the old one
+pub fn copy_profile(from: &str, to: &str) -> Result<(), io::Error> {
+    check_new_name(to)?;
+    copy_dir(&data_dir(from)?, &data_dir(to)?)?;
+
+    let config = config_dir(from)?;
+    if config.exists() {
+        copy_dir(&config, &config_dir(to)?)?;
+    }
+    Ok(())
+}
+
+pub fn delete_profile(name: &str) -> Result<(), io::Error> {
+    fs::remove_dir_all(data_dir(name)?)?;
+
+    let config = config_dir(name)?;
+    if config.exists() {
+        fs::remove_dir_all(config)?;
+    }
+    Ok(())
+}
+
+// the name is a part of the path, so only the simple names are allowed
+pub fn validate_name(name: &str) -> Result<(), io::Error> {
+    let is_valid = !name.is_empty()
+        && name.chars().count() <= 32
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '-' || ch == '_');
+
+    if !is_valid {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "\"{name}\" is not a valid profile name, use up to 32 letters, digits, - and _"
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn check_new_name(name: &str) -> Result<(), io::Error> {
+    validate_name(name)?;
+    if data_dir(name)?.exists() {
+        return Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("The profile \"{name}\" already exists"),
+        ));
+    }
+    Ok(())
+}
+
+// the versions without the profiles stored the files right in the data directory, they become
+// the files of the default profile
+pub fn migrate_to_default() -> Result<(), io::Error> {
+    let root = xdg_dir("XDG_DATA_HOME", ".local/share")?;
+    if root.join(PROFILES_DIR).exists() {
+        return Ok(());
+    }
+
+    let default = data_dir(DEFAULT_PROFILE)?;
+    fs::create_dir_all(&default)?;
+    for entry in fs::read_dir(&root)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::rename(entry.path(), default.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), io::Error> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
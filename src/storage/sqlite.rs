@@ -0,0 +1,453 @@
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, TimeZone};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    app::{
+        history::{Keystroke, SessionRecord},
+        typing_screen::{JSONLetterInfo, JSONResults},
+    },
+    misc::MyHasher,
+};
+
+use super::{migrations::SCHEMA_VERSION, ResultsStore};
+
+// the presses of every key for every expected letter of every session
+type Confusions = HashMap<i64, HashMap<char, HashMap<char, usize, MyHasher>>>;
+// the pressed keys of every session in the order of pressing
+type Keystrokes = HashMap<i64, Vec<Keystroke>>;
+// the change of the tables from the previous version
+type Upgrade = fn(&Connection) -> Result<(), rusqlite::Error>;
+
+// the version of the tables is stored in the user_version of the database
+// 1 - the first tables, 2 - raw, net wpm and cpm, 3 - free mode and corrections,
+// 4 - the time of the test in utc milliseconds, so the tests are sorted by the real time
+const DB_VERSION: i32 = 4;
+
+// every test is one row in the sessions table, the presses of the keys are stored in the
+// confusions table and every pressed key in the keystrokes table, so they can be queried with sql
+pub struct SqliteStore {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: PathBuf) -> Result<SqliteStore, io::Error> {
+        let conn = Connection::open(&path).map_err(to_io)?;
+        let is_new = conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sessions'",
+                [],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(to_io)?
+            .is_none();
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id INTEGER PRIMARY KEY,
+                timestamp TEXT NOT NULL,
+                timestamp_ms INTEGER NOT NULL DEFAULT 0,
+                text TEXT NOT NULL,
+                mode TEXT NOT NULL,
+                chars INTEGER NOT NULL,
+                presses INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                wpm REAL NOT NULL,
+                raw_wpm REAL NOT NULL DEFAULT 0,
+                net_wpm REAL NOT NULL DEFAULT 0,
+                cpm REAL NOT NULL DEFAULT 0,
+                total_accuracy REAL NOT NULL,
+                free INTEGER NOT NULL DEFAULT 0,
+                corrections INTEGER NOT NULL DEFAULT 0
+            );
+            -- how many times the key was pressed, when the expected letter should be typed
+            CREATE TABLE IF NOT EXISTS confusions (
+                session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+                expected TEXT NOT NULL,
+                pressed TEXT NOT NULL,
+                count INTEGER NOT NULL
+            );
+            -- every pressed key of the session, position is the order of pressing
+            CREATE TABLE IF NOT EXISTS keystrokes (
+                session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                expected TEXT NOT NULL,
+                pressed TEXT NOT NULL,
+                offset_ms INTEGER NOT NULL,
+                correct INTEGER NOT NULL
+            );
+            PRAGMA foreign_keys = ON;",
+        )
+        .map_err(to_io)?;
+        // the new tables already have all columns, the old ones are upgraded later
+        if is_new {
+            conn.pragma_update(None, "user_version", DB_VERSION)
+                .map_err(to_io)?;
+        }
+
+        Ok(SqliteStore { path, conn })
+    }
+
+    fn read_confusions(&self) -> Result<Confusions, rusqlite::Error> {
+        let mut confusions: Confusions = HashMap::new();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT session_id, expected, pressed, count FROM confusions")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, usize>(3)?,
+            ))
+        })?;
+
+        for row in rows {
+            let (session_id, expected, pressed, count) = row?;
+            // the letters are always stored as one character
+            let (Some(expected), Some(pressed)) = (expected.chars().next(), pressed.chars().next())
+            else {
+                continue;
+            };
+            confusions
+                .entry(session_id)
+                .or_default()
+                .entry(expected)
+                .or_insert_with(|| HashMap::with_hasher(MyHasher::new()))
+                .insert(pressed, count);
+        }
+        Ok(confusions)
+    }
+
+    fn read_keystrokes(&self) -> Result<Keystrokes, rusqlite::Error> {
+        let mut keystrokes: Keystrokes = HashMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT session_id, expected, pressed, offset_ms, correct FROM keystrokes
+            ORDER BY session_id, position",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, bool>(4)?,
+            ))
+        })?;
+
+        for row in rows {
+            let (session_id, expected, pressed, offset_ms, correct) = row?;
+            let (Some(expected), Some(pressed)) = (expected.chars().next(), pressed.chars().next())
+            else {
+                continue;
+            };
+            keystrokes.entry(session_id).or_default().push(Keystroke {
+                expected,
+                pressed,
+                offset_ms,
+                correct,
+            });
+        }
+        Ok(keystrokes)
+    }
+
+    fn get_version(&self) -> Result<i32, io::Error> {
+        self.conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(to_io)
+    }
+
+    fn has_column(&self, column: &str) -> Result<bool, io::Error> {
+        self.conn
+            .prepare("SELECT 1 FROM pragma_table_info('sessions') WHERE name = ?1")
+            .and_then(|mut stmt| stmt.exists([column]))
+            .map_err(to_io)
+    }
+
+    // the databases before the user_version had no version, so it is found by the columns once
+    fn guess_version(&self) -> Result<i32, io::Error> {
+        let version = match (self.has_column("cpm")?, self.has_column("corrections")?) {
+            (false, _) => 1,
+            (true, false) => 2,
+            (true, true) => 3,
+        };
+        Ok(version)
+    }
+}
+
+// the databases of the older versions have no raw, net wpm and cpm and their wpm was the
+// amount of the words of the text per minute
+fn upgrade_speeds(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "ALTER TABLE sessions ADD COLUMN raw_wpm REAL NOT NULL DEFAULT 0;
+        ALTER TABLE sessions ADD COLUMN net_wpm REAL NOT NULL DEFAULT 0;
+        ALTER TABLE sessions ADD COLUMN cpm REAL NOT NULL DEFAULT 0;",
+    )?;
+
+    let sessions = conn
+        .prepare("SELECT id, chars, presses, duration_ms FROM sessions")?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, usize>(1)?,
+                row.get::<_, usize>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+    for (id, chars, presses, duration_ms) in sessions {
+        let mut results = JSONResults::new();
+        results.set_speeds(chars, presses, duration_ms);
+        conn.execute(
+            "UPDATE sessions SET wpm = ?1, raw_wpm = ?2, net_wpm = ?3, cpm = ?4 WHERE id = ?5",
+            params![
+                results.wpm,
+                results.raw_wpm,
+                results.net_wpm,
+                results.cpm,
+                id
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+// the tests before the free mode had no corrections
+fn upgrade_corrections(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "ALTER TABLE sessions ADD COLUMN free INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE sessions ADD COLUMN corrections INTEGER NOT NULL DEFAULT 0;",
+    )
+}
+
+// the text timestamps have the local offset of the moment of the test, so they were sorted
+// wrong after the change of the time zone or the daylight saving time
+fn upgrade_timestamps(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch("ALTER TABLE sessions ADD COLUMN timestamp_ms INTEGER NOT NULL DEFAULT 0;")?;
+
+    let sessions = conn
+        .prepare("SELECT id, timestamp FROM sessions")?
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+    for (id, timestamp) in sessions {
+        let timestamp = DateTime::parse_from_rfc3339(&timestamp).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, err.into())
+        })?;
+        conn.execute(
+            "UPDATE sessions SET timestamp_ms = ?1 WHERE id = ?2",
+            params![timestamp.timestamp_millis(), id],
+        )?;
+    }
+    Ok(())
+}
+
+impl ResultsStore for SqliteStore {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read_history(&self) -> Result<Vec<SessionRecord>, io::Error> {
+        let mut confusions = self.read_confusions().map_err(to_io)?;
+        let mut keystrokes = self.read_keystrokes().map_err(to_io)?;
+
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, timestamp_ms, text, mode, chars, presses, duration_ms, wpm, raw_wpm,
+                net_wpm, cpm, total_accuracy, free, corrections FROM sessions
+                ORDER BY timestamp_ms, id",
+            )
+            .map_err(to_io)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, usize>(4)?,
+                    row.get::<_, usize>(5)?,
+                    row.get::<_, i64>(6)?,
+                    [
+                        row.get::<_, f64>(7)?,
+                        row.get::<_, f64>(8)?,
+                        row.get::<_, f64>(9)?,
+                        row.get::<_, f64>(10)?,
+                    ],
+                    row.get::<_, f64>(11)?,
+                    (row.get::<_, bool>(12)?, row.get::<_, usize>(13)?),
+                ))
+            })
+            .map_err(to_io)?;
+
+        let mut history = vec![];
+        for row in rows {
+            let (
+                id,
+                timestamp,
+                text,
+                mode,
+                chars,
+                presses,
+                duration_ms,
+                speeds,
+                total_accuracy,
+                (free, corrections),
+            ) = row.map_err(to_io)?;
+            let [wpm, raw_wpm, net_wpm, cpm] = speeds;
+
+            let keystrokes = keystrokes.remove(&id).unwrap_or_default();
+            let letters_info = confusions
+                .remove(&id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(ch, presses)| (ch, JSONLetterInfo::from_presses(ch, presses)))
+                .collect();
+
+            let mut results = JSONResults {
+                wpm,
+                raw_wpm,
+                net_wpm,
+                cpm,
+                total_accuracy,
+                corrections,
+                letters_info,
+            };
+            // the latencies are not stored, because they are always the same as in the keystrokes
+            results.set_latencies(&keystrokes);
+
+            history.push(SessionRecord {
+                schema_version: SCHEMA_VERSION,
+                timestamp: Local
+                    .timestamp_millis_opt(timestamp)
+                    .single()
+                    .ok_or(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("The time of the test {id} is wrong: {timestamp}"),
+                    ))?,
+                text,
+                mode: serde_json::from_value(serde_json::Value::String(mode))?,
+                chars,
+                presses,
+                duration_ms,
+                keystrokes,
+                free,
+                results,
+            });
+        }
+        Ok(history)
+    }
+
+    fn append_session(&self, record: &SessionRecord) -> Result<(), io::Error> {
+        let mode = serde_json::to_value(record.mode)?;
+        let tx = self.conn.unchecked_transaction().map_err(to_io)?;
+
+        tx.execute(
+            "INSERT INTO sessions
+            (timestamp, timestamp_ms, text, mode, chars, presses, duration_ms, wpm, raw_wpm,
+            net_wpm, cpm, total_accuracy, free, corrections)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![
+                record.timestamp.to_rfc3339(),
+                record.timestamp.timestamp_millis(),
+                record.text,
+                mode.as_str(),
+                record.chars,
+                record.presses,
+                record.duration_ms,
+                record.results.wpm,
+                record.results.raw_wpm,
+                record.results.net_wpm,
+                record.results.cpm,
+                record.results.total_accuracy,
+                record.free,
+                record.results.corrections,
+            ],
+        )
+        .map_err(to_io)?;
+        let session_id = tx.last_insert_rowid();
+
+        for (expected, info) in record.results.letters_info.iter() {
+            for (pressed, count) in info.letter_accuracies.iter() {
+                tx.execute(
+                    "INSERT INTO confusions (session_id, expected, pressed, count)
+                    VALUES (?1, ?2, ?3, ?4)",
+                    params![session_id, expected.to_string(), pressed.to_string(), count],
+                )
+                .map_err(to_io)?;
+            }
+        }
+
+        for (position, keystroke) in record.keystrokes.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO keystrokes (session_id, position, expected, pressed, offset_ms, correct)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    session_id,
+                    position,
+                    keystroke.expected.to_string(),
+                    keystroke.pressed.to_string(),
+                    keystroke.offset_ms,
+                    keystroke.correct,
+                ],
+            )
+            .map_err(to_io)?;
+        }
+
+        tx.commit().map_err(to_io)
+    }
+
+    // every step is done in its own transaction together with the new version, so the
+    // interrupted upgrade continues from the last finished step
+    fn upgrade(&self) -> Result<(), io::Error> {
+        let mut version = match self.get_version()? {
+            0 => self.guess_version()?,
+            version => version,
+        };
+        let steps: [(i32, Upgrade); 3] = [
+            (2, upgrade_speeds),
+            (3, upgrade_corrections),
+            (4, upgrade_timestamps),
+        ];
+
+        for (step_version, step) in steps {
+            if version >= step_version {
+                continue;
+            }
+            let tx = self.conn.unchecked_transaction().map_err(to_io)?;
+            step(&tx).map_err(to_io)?;
+            tx.pragma_update(None, "user_version", step_version)
+                .map_err(to_io)?;
+            tx.commit().map_err(to_io)?;
+            version = step_version;
+        }
+        // the guessed version of the newest tables is stored too
+        if self.get_version()? != version {
+            self.conn
+                .pragma_update(None, "user_version", version)
+                .map_err(to_io)?;
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), io::Error> {
+        self.conn
+            .execute_batch("DELETE FROM keystrokes; DELETE FROM confusions; DELETE FROM sessions;")
+            .map_err(to_io)
+    }
+}
+
+fn to_io(err: rusqlite::Error) -> io::Error {
+    io::Error::other(err)
+}
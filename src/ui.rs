@@ -1,19 +1,26 @@
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style, Stylize},
+    symbols::Marker,
     text::{Line, Span, Text},
-    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+    widgets::{
+        Axis, Block, BorderType, Borders, Cell, Chart, Clear, Dataset, GraphType, List, ListItem,
+        ListState, Paragraph, Row, Table, Wrap,
+    },
     Frame,
 };
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, BufReader},
-};
+use std::{collections::HashMap, io};
 
 use crate::{
-    app::{typing_screen::JSONResults, App, Screens},
-    misc::get_color_by_accuracy,
+    app::{
+        confusions::TOP_CONFUSIONS,
+        ngrams::MIN_OCCURRENCES,
+        progress::MOVING_AVERAGE,
+        typing_screen::{FailReason, JSONResults, LINE_BREAK},
+        App, Screens,
+    },
+    cli::Challenge,
+    config::Config,
 };
 use crate::{misc::MyHasher, widgets::keyboard::*};
 
@@ -31,19 +38,45 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         .borders(Borders::ALL)
         .style(Style::default());
 
-    let title = Paragraph::new(Text::styled(
-        app.get_current_screen().as_title(),
-        Style::default().fg(Color::Green),
-    ))
-    .alignment(Alignment::Center)
-    .block(title_block);
+    // the main screen shows, whose results and which mode are used and if the new texts are coming
+    let title = match app.get_current_screen() {
+        Screens::Main => format!(
+            "{} - {} - {}{}{}{}",
+            Screens::Main.as_title(),
+            app.get_storage().profile(),
+            app.get_config().mode.as_str(),
+            match app.get_config().free {
+                true => " - free",
+                false => "",
+            },
+            get_challenge_title(app.get_config()),
+            match app.is_fetching_texts() {
+                true => " - getting the new texts...",
+                false => "",
+            }
+        ),
+        // the global results are only of the current mode
+        screen @ (Screens::GlobalResultMain | Screens::LetterResult) => {
+            format!("{} - {}", screen.as_title(), app.get_config().mode.as_str())
+        }
+        Screens::Typing => format!(
+            "{}{}",
+            Screens::Typing.as_title(),
+            get_challenge_title(app.get_config())
+        ),
+        screen => screen.as_title().to_string(),
+    };
+    let title = Paragraph::new(Text::styled(title, Style::default().fg(Color::Green)))
+        .alignment(Alignment::Center)
+        .block(title_block);
 
     let footer_block = Block::default()
         .borders(Borders::ALL)
         .style(Style::default());
 
     let footer = Paragraph::new(Text::styled(
-        app.get_current_screen().get_keys_hints(),
+        app.get_current_screen()
+            .get_keys_hints(&app.get_config().keys),
         Style::default().fg(Color::White),
     ))
     .alignment(Alignment::Center)
@@ -54,46 +87,109 @@ pub fn ui(f: &mut Frame, app: &mut App) {
 
     match app.get_current_screen() {
         Screens::Typing => {
-            let main_part = app.get_typing_text().alignment(Alignment::Center);
             let layout = Layout::vertical([Constraint::Percentage(50), Constraint::Length(14)])
                 .split(chunks[1]);
+            let text_layout = Layout::vertical([
+                Constraint::Length(2),
+                Constraint::Length(TEXT_ROWS as u16 + 1),
+                Constraint::Min(1),
+            ])
+            .split(layout[0]);
+
+            // the results of the typed part, they are updated while typing
+            let stats = app.get_live_stats();
+            // the timed modes count down and have no end of the text
+            let time = match (stats.remaining_ms, stats.words) {
+                (Some(remaining_ms), _) => format!(
+                    "{}s left   {} chars typed",
+                    (remaining_ms as f64 / 1000.0).ceil(),
+                    stats.typed
+                ),
+                (None, Some((typed, count))) => format!(
+                    "{:.1}s   {typed}/{count} words",
+                    stats.elapsed_ms as f64 / 1000.0,
+                ),
+                (None, None) => format!(
+                    "{:.1}s   {}% typed",
+                    stats.elapsed_ms as f64 / 1000.0,
+                    stats.progress
+                ),
+            };
+            let status = Line::styled(
+                format!(
+                    "{} wpm   {}% accuracy   {} {}{}   {time}",
+                    stats.wpm,
+                    stats.accuracy,
+                    stats.errors,
+                    match stats.errors {
+                        1 => "error",
+                        _ => "errors",
+                    },
+                    match stats.corrections {
+                        Some(1) => "   1 correction".to_string(),
+                        Some(corrections) => format!("   {corrections} corrections"),
+                        None => String::new(),
+                    },
+                ),
+                Style::new().fg(match stats.remaining_ms {
+                    Some(remaining_ms) if remaining_ms <= 5000 => Color::Red,
+                    _ => Color::White,
+                }),
+            );
+            f.render_widget(status.centered(), text_layout[0]);
+
+            if let Some(diff) = app.get_ghost_diff() {
+                let chars = match diff.abs() {
+                    1 => "char",
+                    _ => "chars",
+                };
+                let ghost = match diff {
+                    0 => Line::styled("even with the ghost", Style::new().fg(Color::White)),
+                    diff if diff > 0 => Line::styled(
+                        format!("ahead of the ghost by {diff} {chars}"),
+                        Style::new().fg(Color::Green),
+                    ),
+                    diff => Line::styled(
+                        format!("behind the ghost by {} {chars}", -diff),
+                        Style::new().fg(Color::Red),
+                    ),
+                };
+                f.render_widget(ghost.centered(), text_layout[2]);
+            }
 
             let mut tapped_letter = HashMap::with_hasher(MyHasher::new());
             tapped_letter.insert(app.get_pressed_letter(), 101.0);
 
             f.render_stateful_widget(
-                Keyboard,
+                Keyboard::new(app.get_config().layout, app.get_config().colors),
                 layout[1],
                 &mut KeyboardState::new(tapped_letter, app.get_uppercase()),
             );
-            f.render_widget(main_part, layout[0]);
+            render_typing_text(f, text_layout[1], |width| app.get_typing_text(width));
         }
+        Screens::ReplayList => render_replay_list(f, &chunks[1], app),
+        Screens::Replay => render_replay(f, &chunks[1], app),
+        Screens::Ngrams => render_ngrams(f, &chunks[1], app),
+        Screens::Progress => render_progress(f, &chunks[1], app),
+        Screens::Confusions => render_confusions(f, &chunks[1], app),
+        Screens::TypingFailed => render_failure(f, app),
         Screens::TypingResult => {
-            if let Err(err) = render_results(
-                f,
-                &chunks[1],
-                app.get_uppercase(),
-                None,
-                Some(app.get_last_results()),
-            ) {
+            if let Err(err) = render_results(f, &chunks[1], app, None, Some(app.get_last_results()))
+            {
                 app.alert(err.to_string());
                 alert(f, app);
             }
         }
         Screens::GlobalResultMain => {
-            if let Err(err) = render_results(f, &chunks[1], app.get_uppercase(), None, None) {
+            if let Err(err) = render_results(f, &chunks[1], app, None, None) {
                 app.alert(err.to_string());
                 alert(f, app);
             }
         }
         Screens::LetterResult => {
-            if let Err(err) = render_results(
-                f,
-                &chunks[1],
-                app.get_uppercase(),
-                Some(app.get_pressed_letter()),
-                None,
-            ) {
+            if let Err(err) =
+                render_results(f, &chunks[1], app, Some(app.get_pressed_letter()), None)
+            {
                 app.alert(err.to_string());
                 alert(f, app);
             }
@@ -103,10 +199,129 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             render_logo(f, &chunks[1]);
         }
         Screens::Alert => alert(f, app),
+        Screens::Info => render_popup(
+            f,
+            "Info",
+            format!(
+                "\n\n{}\n\nTap any letter to exit this window.",
+                app.get_alert_text()
+            ),
+            Color::Green,
+        ),
+        Screens::Confirm => confirm(f, app),
+        Screens::Profiles => render_profiles(f, &chunks[1], app),
+        Screens::ProfileName => {
+            render_profiles(f, &chunks[1], app);
+            render_popup(
+                f,
+                app.get_profiles().name_action.as_str(),
+                format!("\n\nName: {}_", app.get_profiles().name_input),
+                Color::White,
+            );
+        }
+    };
+}
+
+// the long texts are wrapped into the rows of this width, so the paragraphs can be read
+const TEXT_WIDTH: u16 = 80;
+// how many rows of the text are shown around the row with the cursor
+const TEXT_ROWS: usize = 5;
+
+// the row with the cursor stays in the middle, the text scrolls up while typing
+fn render_typing_text<'a>(
+    f: &mut Frame,
+    area: Rect,
+    get_text: impl Fn(usize) -> (Vec<Line<'a>>, usize),
+) {
+    let width = area.width.saturating_sub(4).min(TEXT_WIDTH);
+    let (lines, cursor_row) = get_text(width as usize);
+    let rows = TEXT_ROWS.min(area.height as usize);
+    let first = cursor_row.saturating_sub(rows / 2);
+
+    // the short text is in the middle of the screen, the rows of the longer one start on the
+    // same column
+    let (area, alignment) = match lines.len() {
+        1 => (area, Alignment::Center),
+        _ => (
+            Rect {
+                x: area.x + (area.width - width) / 2,
+                width,
+                ..area
+            },
+            Alignment::Left,
+        ),
     };
+    let text = lines
+        .into_iter()
+        .skip(first)
+        .take(rows)
+        .collect::<Vec<Line>>();
+    f.render_widget(Paragraph::new(text).alignment(alignment), area);
+}
+
+fn get_challenge_title(config: &Config) -> String {
+    match config.challenge {
+        Challenge::None => String::new(),
+        Challenge::AccuracyGate => format!(" - accuracy gate {}%", config.gate),
+        challenge => format!(" - {}", challenge.as_str()),
+    }
+}
+
+// why the challenge was failed and how far the user came
+fn render_failure(f: &mut Frame, app: &App) {
+    let Some(failure) = app.get_failure() else {
+        return;
+    };
+    let reason = match failure.reason {
+        FailReason::Mistake { expected, pressed } => format!(
+            "The first mistake: {} instead of {}",
+            show_key(pressed),
+            show_key(expected)
+        ),
+        FailReason::Accuracy { accuracy, gate } => {
+            format!("The accuracy {accuracy}% is below the gate of {gate}%")
+        }
+    };
+    let stats = &failure.stats;
+    // the popup covers the footer with the keys
+    render_popup(
+        f,
+        Screens::TypingFailed.as_title(),
+        format!(
+            "\n\n{reason}\n\n{} wpm   {}% accuracy\n{} chars typed in {:.1}s\n\nThe test is not saved.\n\n{}",
+            stats.wpm,
+            stats.accuracy,
+            stats.typed,
+            stats.elapsed_ms as f64 / 1000.0,
+            Screens::TypingFailed.get_keys_hints(&app.get_config().keys)
+        ),
+        Color::Red,
+    );
 }
 
 fn alert(f: &mut Frame, app: &mut App) {
+    render_popup(
+        f,
+        "Error",
+        format!(
+            "\n\nErr: {}\n\nTap any letter to exit this window.",
+            app.get_alert_text()
+        ),
+        Color::Red,
+    );
+}
+
+fn confirm(f: &mut Frame, app: &App) {
+    render_popup(
+        f,
+        "Confirm",
+        format!("\n\n{}\n\ny - yes, n - no", app.get_alert_text()),
+        Color::White,
+    );
+}
+
+// the window in the middle of the screen over all the other widgets
+fn render_popup(f: &mut Frame, title: &str, text: String, color: Color) {
     let main_chunks = Layout::vertical([
         Constraint::Percentage(20),
         Constraint::Min(1),
@@ -114,7 +329,7 @@ fn alert(f: &mut Frame, app: &mut App) {
     ])
     .split(f.size());
 
-    let alert_chunk = Layout::horizontal([
+    let popup_chunk = Layout::horizontal([
         Constraint::Percentage(30),
         Constraint::Min(1),
         Constraint::Percentage(30),
@@ -122,23 +337,434 @@ fn alert(f: &mut Frame, app: &mut App) {
     .split(main_chunks[1]);
 
     let block = Block::bordered()
-        .title_top("Error")
+        .title_top(title)
         .border_type(BorderType::Rounded)
         .bg(Color::Black);
 
-    let err_text = Paragraph::new(Text::styled(
+    let popup_text = Paragraph::new(Text::styled(text, Style::new().fg(color)))
+        .wrap(Wrap { trim: true })
+        .alignment(Alignment::Center)
+        .block(block);
+
+    f.render_widget(Clear, f.size());
+    f.render_widget(popup_text, popup_chunk[1]);
+}
+
+// the tests, that can be replayed, the newest at first
+fn render_replay_list(f: &mut Frame, area: &Rect, app: &App) {
+    let replays = app.get_replays();
+    let items = replays
+        .items
+        .iter()
+        .map(|item| {
+            ListItem::new(format!(
+                "{}  {:>6} wpm  {:>5}%  {}",
+                item.timestamp.format("%Y-%m-%d %H:%M"),
+                item.wpm,
+                item.total_accuracy,
+                item.text
+            ))
+        })
+        .collect::<Vec<ListItem>>();
+
+    let list = List::new(items)
+        .highlight_style(Style::new().fg(Color::Black).bg(Color::Green))
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Tests")
+                .title_alignment(Alignment::Center),
+        );
+    let chunk = Layout::horizontal([Constraint::Min(1)])
+        .horizontal_margin(5)
+        .split(*area)[0];
+
+    f.render_stateful_widget(
+        list,
+        chunk,
+        &mut ListState::default().with_selected(Some(replays.selected)),
+    );
+}
+
+// the test is shown like while typing, with the state of the replay under the text
+fn render_replay(f: &mut Frame, area: &Rect, app: &App) {
+    let Some(replay) = app.get_replay() else {
+        return;
+    };
+    let layout = Layout::vertical([
+        Constraint::Percentage(50),
+        Constraint::Length(2),
+        Constraint::Length(14),
+    ])
+    .split(*area);
+
+    let (position, keystrokes) = replay.get_position();
+    let state = Span::styled(
         format!(
-            "\n\nErr: {}\n\nTap any letter to exit this window.",
-            app.get_alert_text()
+            "{} {}x   keystroke {position}/{keystrokes}   {:.1}s / {:.1}s   ",
+            if replay.is_playing() { "▶" } else { "⏸" },
+            replay.get_speed(),
+            replay.get_elapsed_ms() as f64 / 1000.0,
+            replay.get_duration_ms() as f64 / 1000.0,
         ),
-        Style::new().fg(Color::Red),
-    ))
-    .wrap(Wrap { trim: true })
-    .alignment(Alignment::Center)
-    .block(block);
+        Style::new().fg(Color::White),
+    );
+    // the long pauses show, where the user has hesitated
+    let pause = match replay.get_last_pause_ms() {
+        Some(pause) => Span::styled(
+            format!("pause before the key: {pause} ms"),
+            match pause > replay.get_average_pause_ms() * 2 {
+                true => Style::new().fg(Color::Red),
+                false => Style::new().fg(Color::Green),
+            },
+        ),
+        None => Span::default(),
+    };
 
-    f.render_widget(Clear, f.size());
-    f.render_widget(err_text, alert_chunk[1]);
+    let mut tapped_letter = HashMap::with_hasher(MyHasher::new());
+    let mut is_uppercase = false;
+    if let Some(keystroke) = replay.get_last_keystroke() {
+        tapped_letter.insert(keystroke.pressed, 101.0);
+        is_uppercase = app.get_config().layout.is_uppercase(keystroke.pressed);
+    }
+
+    render_typing_text(f, layout[0], |width| replay.get_text_to_render(width));
+    f.render_widget(Line::from(vec![state, pause]).centered(), layout[1]);
+    f.render_stateful_widget(
+        Keyboard::new(app.get_config().layout, app.get_config().colors),
+        layout[2],
+        &mut KeyboardState::new(tapped_letter, is_uppercase),
+    );
+}
+
+// the slowest and the most mistyped letter sequences side by side
+fn render_ngrams(f: &mut Frame, area: &Rect, app: &App) {
+    let ngrams = app.get_ngrams();
+    let chunks = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .horizontal_margin(5)
+        .split(*area);
+
+    let slowest = ngrams
+        .slowest
+        .iter()
+        .map(|info| {
+            Line::styled(
+                format!(
+                    "{:<5}{:>7} ms{:>8}x{:>8}% errors",
+                    info.ngram,
+                    info.get_average_transition(),
+                    info.occurrences,
+                    info.get_error_rate()
+                ),
+                Style::new().fg(app
+                    .get_config()
+                    .latency
+                    .get_color(info.get_average_transition() / (ngrams.size - 1) as f64)),
+            )
+        })
+        .collect::<Vec<Line>>();
+    let most_errors = ngrams
+        .most_errors
+        .iter()
+        .map(|info| {
+            Line::styled(
+                format!(
+                    "{:<5}{:>7}% errors{:>8}x{:>8} ms",
+                    info.ngram,
+                    info.get_error_rate(),
+                    info.occurrences,
+                    info.get_average_transition()
+                ),
+                Style::new().fg(app
+                    .get_config()
+                    .colors
+                    .get_color(100.0 - info.get_error_rate())),
+            )
+        })
+        .collect::<Vec<Line>>();
+
+    for (chunk, lines, title) in [
+        (chunks[0], slowest, format!("Slowest {}", ngrams.get_name())),
+        (
+            chunks[1],
+            most_errors,
+            format!("Most mistyped {}", ngrams.get_name()),
+        ),
+    ] {
+        let lines = match lines.is_empty() {
+            true => vec![Line::styled(
+                format!(
+                    "No {} were typed at least {MIN_OCCURRENCES} times yet",
+                    ngrams.get_name()
+                ),
+                Style::new().fg(Color::White),
+            )],
+            false => lines,
+        };
+        let list = Paragraph::new(lines).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title(title)
+                .title_alignment(Alignment::Center),
+        );
+        f.render_widget(list, chunk);
+    }
+}
+
+// the charts of every value of the tests in the range with the moving average over them
+fn render_progress(f: &mut Frame, area: &Rect, app: &App) {
+    let progress = app.get_progress();
+    let subject = match progress.letter {
+        Some(ch) => format!("the letter '{}'", show_key(ch)),
+        None => "the tests".to_string(),
+    };
+
+    let Some((first, last)) = progress.period else {
+        f.render_widget(
+            Line::styled(
+                format!(
+                    "There are no tests of the {} mode in {}",
+                    app.get_config().mode.as_str(),
+                    progress.range.as_str()
+                ),
+                Style::new().fg(Color::White),
+            )
+            .centered(),
+            *area,
+        );
+        return;
+    };
+
+    let mut constraints = vec![Constraint::Length(2)];
+    constraints.extend(progress.series.iter().map(|_| Constraint::Min(5)));
+    let chunks = Layout::vertical(constraints)
+        .horizontal_margin(5)
+        .split(*area);
+
+    // how the average has changed from the start to the end of the range
+    let changes = progress
+        .series
+        .iter()
+        .filter_map(|series| {
+            let (from, to) = series.get_change()?;
+            Some(format!("{}: {from} -> {to} {}", series.name, series.unit))
+        })
+        .collect::<Vec<String>>()
+        .join("   ");
+    f.render_widget(
+        Text::from(vec![
+            Line::styled(
+                format!("Progress of {subject} in {}", progress.range.as_str()),
+                Style::new().fg(Color::Green),
+            ),
+            Line::styled(changes, Style::new().fg(Color::White)),
+        ])
+        .centered(),
+        chunks[0],
+    );
+
+    let dates = [
+        Span::raw(first.format("%Y-%m-%d").to_string()),
+        Span::raw(last.format("%Y-%m-%d").to_string()),
+    ];
+    for (series, chunk) in progress.series.iter().zip(chunks.iter().skip(1)) {
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(format!("{} ({})", series.name, series.unit))
+            .title_alignment(Alignment::Center);
+        if series.points.is_empty() {
+            f.render_widget(
+                Paragraph::new("No info, it is measured only for the newer tests")
+                    .centered()
+                    .block(block),
+                *chunk,
+            );
+            continue;
+        }
+
+        let average = series.get_moving_average();
+        let datasets = vec![
+            Dataset::default()
+                .name("test")
+                .marker(Marker::Dot)
+                .graph_type(GraphType::Scatter)
+                .style(Style::new().fg(Color::Blue))
+                .data(&series.points),
+            Dataset::default()
+                .name(format!("average of {MOVING_AVERAGE}"))
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::new().fg(Color::Green))
+                .data(&average),
+        ];
+
+        let max_x = series.points.last().map_or(1.0, |(x, _)| x.max(1.0));
+        let (min_y, max_y) = series
+            .points
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), (_, y)| {
+                (min.min(*y), max.max(*y))
+            });
+        let (min_y, max_y) = (min_y.floor(), max_y.ceil().max(min_y.floor() + 1.0));
+
+        let chart = Chart::new(datasets)
+            .block(block)
+            .x_axis(
+                Axis::default()
+                    .style(Style::new().fg(Color::Gray))
+                    .bounds([0.0, max_x])
+                    .labels(dates.to_vec()),
+            )
+            .y_axis(
+                Axis::default()
+                    .style(Style::new().fg(Color::Gray))
+                    .bounds([min_y, max_y])
+                    .labels(vec![
+                        Span::raw(min_y.to_string()),
+                        Span::raw(max_y.to_string()),
+                    ]),
+            );
+        f.render_widget(chart, *chunk);
+    }
+}
+
+// the mistypes of every expected letter with every pressed key, the most often are highlighted
+fn render_confusions(f: &mut Frame, area: &Rect, app: &App) {
+    let confusions = app.get_confusions();
+    let Some(matrix) = confusions.matrix.as_ref() else {
+        return;
+    };
+    let chunks = Layout::horizontal([Constraint::Min(1), Constraint::Length(30)])
+        .horizontal_margin(5)
+        .split(*area);
+
+    let top = matrix.get_top_confusions(TOP_CONFUSIONS);
+    let top_lines = match top.is_empty() {
+        true => vec![Line::styled(
+            "There are no mistypes yet",
+            Style::new().fg(Color::Green),
+        )],
+        false => top
+            .iter()
+            .map(|(expected, pressed, count)| {
+                Line::styled(
+                    format!(
+                        "{} -> {}  {count} {}",
+                        show_key(*expected),
+                        show_key(*pressed),
+                        match count {
+                            1 => "time",
+                            _ => "times",
+                        }
+                    ),
+                    Style::new().fg(Color::Red),
+                )
+            })
+            .collect(),
+    };
+    f.render_widget(
+        Paragraph::new(top_lines).block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .title("Top confusions")
+                .title_alignment(Alignment::Center),
+        ),
+        chunks[1],
+    );
+
+    // the columns, that don't fit, are cut off, they have the least mistypes, if it's sorted by them
+    let fitting = (chunks[0].width.saturating_sub(7) / 5) as usize;
+    let keys = matrix
+        .get_mistyped_keys()
+        .into_iter()
+        .take(fitting)
+        .collect::<Vec<char>>();
+
+    let header = Row::new(
+        ["exp".to_string()]
+            .into_iter()
+            .chain(keys.iter().map(|ch| show_key(*ch))),
+    )
+    .style(Style::new().fg(Color::Green));
+    let rows = matrix
+        .get_mistyped_letters()
+        .into_iter()
+        .skip(confusions.scroll)
+        .map(|expected| {
+            let mut cells = vec![Cell::from(show_key(expected)).fg(Color::Green)];
+            cells.extend(keys.iter().map(|pressed| {
+                let count = matrix.get(expected, *pressed);
+                match count {
+                    _ if expected == *pressed => Cell::from("·").fg(Color::DarkGray),
+                    0 => Cell::from(""),
+                    count if top.contains(&(expected, *pressed, count)) => {
+                        Cell::from(count.to_string())
+                            .fg(Color::Black)
+                            .bg(Color::Red)
+                    }
+                    count => Cell::from(count.to_string()).fg(Color::Yellow),
+                }
+            }));
+            Row::new(cells)
+        })
+        .collect::<Vec<Row>>();
+
+    let widths = vec![Constraint::Length(4); keys.len() + 1];
+    let table = Table::new(rows, widths).header(header).block(
+        Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(format!(
+                "Expected letter x pressed key, sorted {}",
+                confusions.sort.as_str()
+            ))
+            .title_alignment(Alignment::Center),
+    );
+    f.render_widget(table, chunks[0]);
+}
+
+// the space can't be seen in the tables
+fn show_key(ch: char) -> String {
+    match ch {
+        ' ' => "␣".to_string(),
+        '\n' => LINE_BREAK.to_string(),
+        ch => ch.to_string(),
+    }
+}
+
+// the list of the profiles, the current one is marked
+fn render_profiles(f: &mut Frame, area: &Rect, app: &App) {
+    let profiles = app.get_profiles();
+    let lines = profiles
+        .names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let text = match name == app.get_storage().profile() {
+                true => format!("{name} (current)"),
+                false => name.to_string(),
+            };
+            let style = match i == profiles.selected {
+                true => Style::new().fg(Color::Black).bg(Color::Green),
+                false => Style::new().fg(Color::White),
+            };
+            Line::styled(text, style).centered()
+        })
+        .collect::<Vec<Line>>();
+
+    let chunk = Layout::horizontal([
+        Constraint::Percentage(30),
+        Constraint::Min(1),
+        Constraint::Percentage(30),
+    ])
+    .split(*area)[1];
+
+    let list = Paragraph::new(lines).block(
+        Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title("Profiles")
+            .title_alignment(Alignment::Center),
+    );
+    f.render_widget(list, chunk);
 }
 
 // todo rewrite as widget
@@ -174,28 +800,28 @@ fn render_logo(f: &mut Frame, area: &Rect) {
 fn render_results(
     f: &mut Frame,
     area: &Rect,
-    is_uppercase: bool,
+    app: &App,
     choosed_letter: Option<char>,
     typing_results: Option<&JSONResults>,
 ) -> Result<(), io::Error> {
-    let json_results = {
-        let file = File::open("results.json")?;
-        let read_buf = BufReader::new(file);
-        let json_results: JSONResults = serde_json::from_reader(read_buf)?;
-        json_results
-    };
-
+    let config = app.get_config();
+    let is_uppercase = app.get_uppercase();
     let json_results = match typing_results {
         Some(res) => res,
-        None => &json_results,
+        None => &app
+            .get_storage()
+            .results()
+            .read_results(config.weighting, config.mode)?,
     };
 
+    if app.get_show_latency() {
+        render_latencies(f, area, json_results, config, is_uppercase, choosed_letter);
+        return Ok(());
+    }
+
     // if there are letter choosen, then it is the results from one letter
     let mut results = match choosed_letter {
-        Some(ch) => match json_results.get_result_by_letter(ch) {
-            Ok(results) => results,
-            Err(err) => return Err(err),
-        },
+        Some(ch) => json_results.get_result_by_letter(ch)?,
         None => json_results.get_total_results(),
     };
 
@@ -209,12 +835,18 @@ fn render_results(
         None => json_results.total_accuracy,
     };
 
-    let wpm = json_results.wpm;
-
+    // only the tests of the free mode have the corrections
+    let corrections = match (choosed_letter, json_results.corrections) {
+        (None, corrections) if corrections > 0 => format!(", Corrections: {corrections}"),
+        _ => String::new(),
+    };
     let main_info = Line::styled(
         format!(
-            "Speed: {} wpm, Total accuracy: {}%",
-            (wpm * 100.0).round() / 100.0,
+            "Speed: {} wpm (net {}, raw {}), {} cpm, Total accuracy: {}%{corrections}",
+            json_results.wpm,
+            json_results.net_wpm,
+            json_results.raw_wpm,
+            json_results.cpm,
             total_accuracy
         ),
         Style::new().fg(Color::Red),
@@ -238,8 +870,11 @@ fn render_results(
                     return Span::default();
                 }
             }
-            let color = get_color_by_accuracy(*accuracy);
-            Span::styled(format!("{ch}:{accuracy}% "), Style::new().fg(color))
+            let color = config.colors.get_color(*accuracy);
+            Span::styled(
+                format!("{}:{accuracy}% ", show_key(*ch)),
+                Style::new().fg(color),
+            )
         }))
         .centered();
 
@@ -251,6 +886,88 @@ fn render_results(
         .fg(Color::Green)]);
     }
 
+    let mut keyboard_state = match choosed_letter {
+        Some(ch) => {
+            results.insert(ch, 101.0);
+            KeyboardState::new(results, is_uppercase)
+        }
+        None => KeyboardState::new(results, is_uppercase),
+    };
+
+    render_results_parts(
+        f,
+        area,
+        main_info,
+        letters_line,
+        Keyboard::new(config.layout, config.colors),
+        &mut keyboard_state,
+    );
+    Ok(())
+}
+
+// the average time to press every letter instead of the accuracy
+fn render_latencies(
+    f: &mut Frame,
+    area: &Rect,
+    json_results: &JSONResults,
+    config: &Config,
+    is_uppercase: bool,
+    choosed_letter: Option<char>,
+) {
+    let latency = match choosed_letter {
+        Some(ch) => json_results
+            .letters_info
+            .get(&ch)
+            .and_then(|info| Some((info.get_average_latency()?, info.get_median_latency()?))),
+        None => json_results.get_latency(),
+    };
+    let name = match choosed_letter {
+        Some(ch) => format!("Latency of {ch:?}"),
+        None => "Latency".to_string(),
+    };
+    let main_info = match latency {
+        Some((average, median)) => format!("{name}: average {average} ms, median {median} ms"),
+        None => format!("{name}: no info, it is measured only for the newer tests"),
+    };
+    let main_info = Line::styled(main_info, Style::new().fg(Color::Red)).centered();
+
+    let results = json_results.get_latency_results();
+    let mut letters_line = results
+        .iter()
+        .map(|(ch, latency)| (*ch, *latency))
+        .collect::<Vec<(char, f64)>>();
+    // the slowest letters at first
+    letters_line.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    let letters_line = Line::default()
+        .spans(letters_line.iter().map(|(ch, latency)| {
+            let color = config.latency.get_color(*latency);
+            Span::styled(
+                format!("{}:{latency}ms ", show_key(*ch)),
+                Style::new().fg(color),
+            )
+        }))
+        .centered();
+
+    render_results_parts(
+        f,
+        area,
+        main_info,
+        letters_line,
+        Keyboard::with_latency(config.layout, config.latency),
+        &mut KeyboardState::new(results, is_uppercase),
+    );
+}
+
+// the main info, the info about every letter and the keyboard under them
+fn render_results_parts(
+    f: &mut Frame,
+    area: &Rect,
+    main_info: Line,
+    letters_line: Line,
+    keyboard: Keyboard,
+    keyboard_state: &mut KeyboardState,
+) {
     let letters_block = Paragraph::new(letters_line)
         .wrap(Wrap { trim: true })
         .centered()
@@ -276,17 +993,7 @@ fn render_results(
         .horizontal_margin(5)
         .split(upper_chunks[1])[0];
 
-    let mut keyboard_state = match choosed_letter {
-        Some(ch) => {
-            results.insert(ch, 101.0);
-            KeyboardState::new(results, is_uppercase)
-        }
-        None => KeyboardState::new(results, is_uppercase),
-    };
-
     f.render_widget(main_info, upper_chunks[0]);
     f.render_widget(letters_block, letters_chunk);
-    f.render_stateful_widget(Keyboard, main_chunk[2], &mut keyboard_state);
-
-    Ok(())
+    f.render_stateful_widget(keyboard, main_chunk[2], keyboard_state);
 }
@@ -7,9 +7,40 @@ use ratatui::{
     widgets::{Block, BorderType, Paragraph, StatefulWidget, Widget},
 };
 
-use crate::misc::MyHasher;
+use crate::{
+    config::{ColorThresholds, KeyboardLayout, LatencyThresholds},
+    misc::MyHasher,
+};
+
+// what the values of the highlighted keys are
+enum KeyColors {
+    Accuracy(ColorThresholds),
+    Latency(LatencyThresholds),
+}
+
+pub struct Keyboard {
+    layout: KeyboardLayout,
+    colors: KeyColors,
+}
+
+impl Keyboard {
+    // the keys are colored by the accuracy in percents
+    pub fn new(layout: KeyboardLayout, colors: ColorThresholds) -> Keyboard {
+        Keyboard {
+            layout,
+            colors: KeyColors::Accuracy(colors),
+        }
+    }
+
+    // the keys are colored by the average time in milliseconds to press them
+    pub fn with_latency(layout: KeyboardLayout, latency: LatencyThresholds) -> Keyboard {
+        Keyboard {
+            layout,
+            colors: KeyColors::Latency(latency),
+        }
+    }
+}
 
-pub struct Keyboard;
 pub struct KeyboardState {
     keys_to_highlight: HashMap<char, f64, MyHasher>,
     uppercase: bool,
@@ -28,20 +59,17 @@ impl StatefulWidget for Keyboard {
     type State = KeyboardState;
 
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        let letters = match state.uppercase {
-            false => "qwertyuiop[]\\asdfghjkl;\'zxcvbnm,./ ",
-            true => "QWERTYUIOP{}|ASDFGHJKL:\"ZXCVBNM<>? ",
-        };
-
-        let letters = letters
+        let letters = self
+            .layout
+            .get_keys(state.uppercase)
             .chars()
-            .into_iter()
             .map(|ch| {
-                let accuracy = *state.keys_to_highlight.get(&ch).unwrap_or(&0.0);
-                Keycap {
-                    ch,
-                    color: get_color_by_accuracy(accuracy),
-                }
+                let value = *state.keys_to_highlight.get(&ch).unwrap_or(&0.0);
+                let color = match &self.colors {
+                    KeyColors::Accuracy(colors) => colors.get_color(value),
+                    KeyColors::Latency(latency) => latency.get_color(value),
+                };
+                Keycap { ch, color }
             })
             .collect::<Vec<Keycap>>();
 
@@ -64,7 +92,7 @@ impl StatefulWidget for Keyboard {
         let rows_lengths: [usize; 4] = [13, 11, 10, 1];
         // building the layout of the rows
         let keyboard_rows = keyboard_rows
-            .into_iter()
+            .iter()
             .enumerate()
             .map(|(row_i, row)| {
                 // if this the 1,2,3 rows it will be a row with the length 5 so many charachtecrs
@@ -112,14 +140,3 @@ impl Widget for Keycap {
             .render(area, buf);
     }
 }
-
-fn get_color_by_accuracy(accuracy: f64) -> Color {
-    match accuracy {
-        perc if perc == 101.0 => Color::Yellow,
-        perc if perc == 0.0 => Color::Reset,
-        perc if perc >= 80.0 => Color::Green,
-        perc if perc >= 50.0 => Color::Blue,
-        perc if perc <= 50.0 => Color::Red,
-        _ => Color::Reset,
-    }
-}